@@ -1,4 +1,6 @@
-pub fn run(input: &str) -> Result<(), String> {
+use crate::options::Options;
+
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let part1: u32 = calibrate(input, to_digits_part1)?;
     println!("Part 1: {part1}");
     let part2: u32 = calibrate(input, to_digits_part2)?;
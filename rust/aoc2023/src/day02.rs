@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
-pub fn run(input: &str) -> Result<(), String> {
+use crate::options::Options;
+
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let games = read_games(input)?;
     let part1 = part_1(&games);
     println!("Part 1: {part1}");
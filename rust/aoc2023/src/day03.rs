@@ -1,6 +1,8 @@
 use std::collections::{HashMap, HashSet};
 
-pub fn run(input: &str) -> Result<(), String> {
+use crate::options::Options;
+
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let schematic = read_schematic(input);
     let part1 = part1(&schematic);
     println!("Part 1: {part1}");
@@ -1,6 +1,8 @@
 use std::collections::{HashMap, HashSet};
 
-pub fn run(input: &str) -> Result<(), String> {
+use crate::options::Options;
+
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let cards = read_cards(input)?;
     let wins_by_card = wins_by_card(&cards);
     let part1: u32 = wins_by_card.values().map(score).sum();
@@ -1,62 +1,144 @@
-use std::collections::BTreeSet;
+use itertools::Itertools;
 
-use crate::util::read;
+use crate::{options::Options, util::read};
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
     let (seeds, maps) = read_input(input)?;
     let part1 = part1(&seeds, &maps)?;
     println!("Part 1: {part1}");
     let part2 = part2(&seeds, &maps)?;
     println!("Part 2: {part2}");
+    if options.show {
+        let seeds_at_lowest = locations_to_seeds(&maps, (part2, part2 + 1))?;
+        println!(
+            "Seeds reaching location {part2}: {}",
+            seeds_at_lowest
+                .iter()
+                .map(|(from, to)| format!("{from}..{to}"))
+                .join(", ")
+        );
+        match part2_reverse(&seeds, &maps) {
+            Ok(reverse) if reverse != part2 => {
+                return Err(format!("Reverse lookup found {reverse} for part 2"))
+            }
+            Ok(_) => println!("Part 2 verified by reverse lookup"),
+            Err(e) => println!("Could not verify part 2: {e}"),
+        }
+    }
     Ok(())
 }
 
-fn part1(seeds: &Vec<u64>, maps: &Vec<Map>) -> Result<u64, String> {
+fn part1(seeds: &[u64], maps: &[Map]) -> Result<u64, String> {
+    let almanac = compose(&route(maps, "seed", "location")?)?;
     seeds
         .iter()
-        .flat_map(|s| run_conversions((*s, s + 1), maps))
-        .map(|(a, _)| a)
+        .map(|s| almanac.get(*s))
         .min()
         .ok_or("No result found".to_owned())
 }
 
-fn run_conversions(start: (u64, u64), maps: &Vec<Map>) -> Vec<(u64, u64)> {
-    maps.iter().fold(vec![start], |acc, m| {
-        acc.iter()
-            .flat_map(|p| to_conversion_ranges(*p, &m))
-            .collect()
-    })
+fn part2(seeds: &[u64], maps: &[Map]) -> Result<u64, String> {
+    let almanac = compose(&route(maps, "seed", "location")?)?;
+    seed_ranges(seeds)?
+        .iter()
+        .filter_map(|range| almanac.lowest_in(*range))
+        .min()
+        .ok_or("No result found".to_owned())
 }
 
-fn part2(seeds: &Vec<u64>, maps: &Vec<Map>) -> Result<u64, String> {
+/// Alternative strategy for part 2: go through the locations from low to high until one of them
+/// is reached from a seed in the seed ranges. Locations are taken an interval between the edges of
+/// the pieces' images at a time, so every piece reaches either all of an interval or none of it.
+fn part2_reverse(seeds: &[u64], maps: &[Map]) -> Result<u64, String> {
+    let seed_ranges = seed_ranges(seeds)?;
+    let almanac = compose(&route(maps, "seed", "location")?)?;
+    let edges = almanac
+        .images()
+        .flat_map(|(start, end, _)| [Some(start), end])
+        .flatten()
+        .sorted()
+        .dedup()
+        .collect_vec();
+    edges
+        .iter()
+        .copied()
+        .zip(edges.iter().copied().skip(1).chain([u64::MAX]))
+        .find_map(|locations| {
+            almanac
+                .preimage(locations)
+                .iter()
+                .cartesian_product(&seed_ranges)
+                .map(|((from, to), (seed_from, seed_to))| (*from.max(seed_from), *to.min(seed_to)))
+                .filter(|(from, to)| from < to)
+                .map(|(from, _)| almanac.get(from))
+                .min()
+        })
+        .ok_or("No result found".to_owned())
+}
+
+/// Reverse lookup: all seeds that end up at a location in the range `[from, to)`.
+fn locations_to_seeds(maps: &[Map], locations: (u64, u64)) -> Result<Vec<(u64, u64)>, String> {
+    Ok(compose(&route(maps, "seed", "location")?)?.preimage(locations))
+}
+
+fn seed_ranges(seeds: &[u64]) -> Result<Vec<(u64, u64)>, String> {
     match seeds.as_chunks::<2>() {
-        (chunks, []) => chunks
+        (chunks, []) => Ok(chunks
             .iter()
             .map(|[from, len]| (*from, from + len))
-            .flat_map(|s| run_conversions(s, &maps))
-            .map(|(a, _)| a)
-            .min()
-            .ok_or("No result found".to_owned()),
+            .collect()),
         _ => Err("seeds was not of even length".to_owned()),
     }
 }
 
-fn to_conversion_ranges((from, to): (u64, u64), conversion_map: &Map) -> Vec<(u64, u64)> {
-    conversion_map
-        .conversions
-        .iter()
-        .flat_map(|c| [c.source_start, c.source_start + c.length])
-        .filter(|v| *v < to && from <= *v)
-        .chain([from, to])
-        .collect::<BTreeSet<_>>()
-        .iter()
-        .map_windows(|&[from, to]| {
-            (
-                conversion_map.convert(*from),
-                conversion_map.convert(to - 1) + 1,
-            )
-        })
-        .collect::<Vec<_>>()
+/// Finds the maps to apply, in order, to convert values from one category to another. Fails if
+/// there is no way to convert between the categories, or if there is more than one.
+fn route<'a>(maps: &'a [Map], from: &str, to: &str) -> Result<Vec<&'a Map>, String> {
+    fn paths<'a: 'b, 'b>(
+        maps: &'a [Map],
+        from: &str,
+        to: &str,
+        visited: &mut Vec<&'b str>,
+    ) -> Vec<Vec<&'a Map>> {
+        if from == to {
+            return vec![vec![]];
+        }
+        let mut result = Vec::new();
+        for map in maps.iter().filter(|m| m.source == from) {
+            if visited.contains(&map.destination.as_str()) {
+                continue;
+            }
+            visited.push(&map.destination);
+            for mut path in paths(maps, &map.destination, to, visited) {
+                path.insert(0, map);
+                result.push(path);
+            }
+            visited.pop();
+        }
+        result
+    }
+    let mut found = paths(maps, from, to, &mut vec![from]);
+    match found.len() {
+        0 => Err(format!("No conversion from {from} to {to}")),
+        1 => Ok(found.remove(0)),
+        _ => Err(format!(
+            "Conversion from {from} to {to} is ambiguous: {}",
+            found
+                .iter()
+                .map(|path| {
+                    let categories = path.iter().map(|m| m.destination.as_str()).join(" -> ");
+                    format!("{from} -> {categories}")
+                })
+                .join(", ")
+        )),
+    }
+}
+
+/// Composes the maps, in order, into a single map.
+fn compose(maps: &[&Map]) -> Result<IntervalMap, String> {
+    maps.iter().try_fold(IntervalMap::identity(), |acc, m| {
+        Ok(acc.then(&IntervalMap::from_conversions(&m.conversions)?))
+    })
 }
 
 fn read_input(input: &str) -> Result<(Vec<u64>, Vec<Map>), String> {
@@ -85,9 +167,15 @@ fn read_seeds(line: &str) -> Result<Vec<u64>, String> {
 }
 
 fn read_map(map: &str) -> Result<Map, String> {
+    let header = map.lines().next().unwrap_or_default();
+    let (source, destination) = header
+        .strip_suffix(" map:")
+        .and_then(|categories| categories.split_once("-to-"))
+        .ok_or_else(|| format!("Could not read map header '{header}'"))?;
     match &map.lines().collect::<Vec<_>>()[..] {
-        [name, conversions @ ..] => Ok(Map {
-            name: (*name).to_owned(),
+        [_, conversions @ ..] => Ok(Map {
+            source: source.to_owned(),
+            destination: destination.to_owned(),
             conversions: conversions
                 .iter()
                 .map(|&c| read_conversion(c))
@@ -111,26 +199,10 @@ fn read_conversion(conversion: &str) -> Result<Conversion, String> {
 
 #[derive(Debug)]
 struct Map {
-    #[allow(dead_code)]
-    name: String,
+    source: String,
+    destination: String,
     conversions: Vec<Conversion>,
 }
-impl Map {
-    fn convert(&self, value: u64) -> u64 {
-        self.conversions
-            .iter()
-            .find_map(|c| {
-                if c.source_start <= value && value < c.source_start + c.length {
-                    let offset = value - c.source_start;
-                    Some(c.destination_start + offset)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(value)
-    }
-}
-
 #[derive(Debug, Clone)]
 struct Conversion {
     destination_start: u64,
@@ -138,6 +210,140 @@ struct Conversion {
     length: u64,
 }
 
+/// A mapping of values that moves every value in an interval by the same offset. The intervals are
+/// consecutive and cover all values: each piece is the start of an interval and its offset, and
+/// the interval extends up to the start of the next piece (the last one extends indefinitely).
+/// Within a piece the mapping is increasing, so ranges can be mapped by their endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IntervalMap {
+    pieces: Vec<(u64, i64)>,
+}
+impl IntervalMap {
+    fn identity() -> IntervalMap {
+        IntervalMap {
+            pieces: vec![(0, 0)],
+        }
+    }
+
+    fn from_conversions(conversions: &[Conversion]) -> Result<IntervalMap, String> {
+        let mut pieces = Vec::new();
+        let mut covered_until = 0;
+        for c in conversions.iter().sorted_by_key(|c| c.source_start) {
+            if c.source_start < covered_until {
+                return Err(format!("Conversion {c:?} overlaps with another conversion"));
+            }
+            pieces.push((covered_until, 0));
+            pieces.push((
+                c.source_start,
+                c.destination_start as i64 - c.source_start as i64,
+            ));
+            covered_until = c.source_start + c.length;
+        }
+        pieces.push((covered_until, 0));
+        Ok(IntervalMap::normalized(pieces))
+    }
+
+    /// Removes empty pieces and merges consecutive pieces with the same offset.
+    fn normalized(pieces: Vec<(u64, i64)>) -> IntervalMap {
+        let mut result: Vec<(u64, i64)> = Vec::new();
+        for (start, offset) in pieces {
+            if result.last().is_some_and(|(s, _)| *s == start) {
+                result.pop();
+            }
+            if result.last().is_none_or(|(_, o)| *o != offset) {
+                result.push((start, offset));
+            }
+        }
+        IntervalMap { pieces: result }
+    }
+
+    /// The end (exclusive) of the i'th piece, or None if it extends indefinitely.
+    fn end(&self, i: usize) -> Option<u64> {
+        self.pieces.get(i + 1).map(|(start, _)| *start)
+    }
+
+    fn get(&self, value: u64) -> u64 {
+        let i = self.pieces.partition_point(|(start, _)| *start <= value) - 1;
+        shift(value, self.pieces[i].1)
+    }
+
+    /// Splits the range `[from, to)` at the piece boundaries. Returns the start, end and offset of
+    /// every part.
+    fn segments(
+        &self,
+        from: u64,
+        to: Option<u64>,
+    ) -> impl Iterator<Item = (u64, Option<u64>, i64)> + '_ {
+        let first = self.pieces.partition_point(|(start, _)| *start <= from) - 1;
+        (first..self.pieces.len())
+            .take_while(move |i| to.is_none_or(|to| self.pieces[*i].0 < to))
+            .map(move |i| {
+                let start = self.pieces[i].0.max(from);
+                let end = match (self.end(i), to) {
+                    (Some(e), Some(to)) => Some(e.min(to)),
+                    (e, to) => e.or(to),
+                };
+                (start, end, self.pieces[i].1)
+            })
+    }
+
+    /// Maps the range `[from, to)`, resulting in one range per piece it overlaps.
+    fn map_range(&self, (from, to): (u64, u64)) -> Vec<(u64, u64)> {
+        self.segments(from, Some(to))
+            .map(|(start, end, offset)| (shift(start, offset), shift(end.unwrap(), offset)))
+            .collect()
+    }
+
+    /// The lowest value the range `[from, to)` maps to.
+    fn lowest_in(&self, (from, to): (u64, u64)) -> Option<u64> {
+        self.map_range((from, to)).iter().map(|(f, _)| *f).min()
+    }
+
+    /// Composes this map with another one, applying this map first.
+    fn then(&self, next: &IntervalMap) -> IntervalMap {
+        let pieces = self
+            .pieces
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &(start, offset))| {
+                let image_end = self.end(i).map(|e| shift(e, offset));
+                next.segments(shift(start, offset), image_end)
+                    .map(move |(s, _, o)| (shift(s, -offset), offset + o))
+            })
+            .collect();
+        IntervalMap::normalized(pieces)
+    }
+
+    /// The interval every piece maps to: its start, its end (exclusive, or None if it extends
+    /// indefinitely) and the offset that maps it back.
+    fn images(&self) -> impl Iterator<Item = (u64, Option<u64>, i64)> + '_ {
+        (0..self.pieces.len()).map(|i| {
+            let (start, offset) = self.pieces[i];
+            (
+                shift(start, offset),
+                self.end(i).map(|e| shift(e, offset)),
+                -offset,
+            )
+        })
+    }
+
+    /// All values that map into the range `[from, to)`, sorted. There is one range for every piece
+    /// that reaches into `[from, to)`, so different values can map to the same one.
+    fn preimage(&self, (from, to): (u64, u64)) -> Vec<(u64, u64)> {
+        self.images()
+            .filter_map(|(start, end, back)| {
+                let (start, end) = (start.max(from), end.map_or(to, |end| end.min(to)));
+                (start < end).then(|| (shift(start, back), shift(end, back)))
+            })
+            .sorted()
+            .collect()
+    }
+}
+
+fn shift(value: u64, offset: i64) -> u64 {
+    value.wrapping_add_signed(offset)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,9 +388,102 @@ humidity-to-location map:
     }
 
     #[test]
-    fn to_conversion_ranges_test() {
+    fn interval_map_test() {
         let (_, m) = read_input(SAMPLE_INPUT).unwrap();
-        println!("{:?}", to_conversion_ranges((74, 88), m.get(4).unwrap()));
+        let light_to_temperature = IntervalMap::from_conversions(&m[4].conversions).unwrap();
+        assert_eq!(
+            vec![(0, 0), (45, 36), (64, 4), (77, -32), (100, 0)],
+            light_to_temperature.pieces
+        );
+        assert_eq!(78, light_to_temperature.get(74));
+        assert_eq!(
+            vec![(78, 81), (45, 56)],
+            light_to_temperature.map_range((74, 88))
+        );
+    }
+
+    #[test]
+    fn compose_test() {
+        let (_, m) = read_input(SAMPLE_INPUT).unwrap();
+        let almanac = compose(&route(&m, "seed", "location").unwrap()).unwrap();
+        assert_eq!(
+            vec![82, 43, 86, 35],
+            [79, 14, 55, 13].map(|s| almanac.get(s)).to_vec()
+        );
+        assert_eq!(vec![(82, 83)], almanac.preimage((46, 47)));
+    }
+
+    #[test]
+    fn part2_reverse_test() {
+        let (s, m) = read_input(SAMPLE_INPUT).unwrap();
+        assert_eq!(46, part2_reverse(&s, &m).unwrap());
+    }
+
+    #[test]
+    fn locations_to_seeds_test() {
+        let (_, m) = read_input(SAMPLE_INPUT).unwrap();
+        assert_eq!(Ok(vec![(82, 84)]), locations_to_seeds(&m, (46, 48)));
+        assert_eq!(Ok(vec![(62, 66)]), locations_to_seeds(&m, (56, 60)));
+    }
+
+    #[test]
+    fn route_test() {
+        let (s, mut m) = read_input(SAMPLE_INPUT).unwrap();
+        m.reverse();
+        assert_eq!(35, part1(&s, &m).unwrap());
+        let soil_to_humidity = route(&m, "soil", "humidity").unwrap();
+        assert_eq!(
+            vec!["fertilizer", "water", "light", "temperature", "humidity"],
+            soil_to_humidity
+                .iter()
+                .map(|m| m.destination.as_str())
+                .collect_vec()
+        );
+        assert_eq!(
+            Err("No conversion from location to seed".to_owned()),
+            route(&m, "location", "seed").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn route_ambiguous_test() {
+        let shortcut = read_map("seed-to-water map:\n1 2 3").unwrap();
+        let (_, mut m) = read_input(SAMPLE_INPUT).unwrap();
+        m.push(shortcut);
+        assert_eq!(
+            Err("Conversion from seed to water is ambiguous: \
+                seed -> soil -> fertilizer -> water, seed -> water"
+                .to_owned()),
+            route(&m, "seed", "water").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn preimage_test() {
+        // 10..20 maps onto 5..15, overlapping with 5..10 and 15..20 mapping to themselves
+        let not_injective = IntervalMap {
+            pieces: vec![(0, 0), (10, -5), (20, 0)],
+        };
+        assert_eq!(
+            vec![(5, 10), (10, 15), (20, 22)],
+            not_injective
+                .preimage((5, 10))
+                .into_iter()
+                .chain(not_injective.preimage((20, 22)))
+                .collect_vec()
+        );
+        assert_eq!(vec![(7, 8), (12, 13)], not_injective.preimage((7, 8)));
+        assert_eq!(Vec::<(u64, u64)>::new(), not_injective.preimage((7, 7)));
+    }
+
+    #[test]
+    fn part2_reverse_not_injective_test() {
+        // Seeds 10 to 19 all end up at the same locations as seeds 5 to 14
+        let input = "seeds: 12 5\n\nseed-to-location map:\n5 10 10";
+        let (s, m) = read_input(input).unwrap();
+        assert_eq!(Ok(7), part2_reverse(&s, &m));
+        assert_eq!(part2(&s, &m), part2_reverse(&s, &m));
+        assert_eq!(Ok(vec![(7, 8), (12, 13)]), locations_to_seeds(&m, (7, 8)));
     }
 
     #[test]
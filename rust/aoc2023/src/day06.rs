@@ -1,8 +1,11 @@
-use crate::util::read;
+use crate::{
+    options::Options,
+    util::{math::isqrt, read},
+};
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let races = read_races(input)?;
-    let part1 = part1(&races);
+    let part1 = part1(&races)?;
     println!("Part 1: {part1}");
     let amended_race = read_amended(input)?;
     let part2 = ways_to_win(&amended_race);
@@ -10,30 +13,81 @@ pub fn run(input: &str) -> Result<(), String> {
     Ok(())
 }
 
-fn part1(races: &Vec<(i64, i64)>) -> i64 {
-    races.iter().map(ways_to_win).product()
+fn part1(races: &[(u128, u128)]) -> Result<u128, String> {
+    races.iter().try_fold(1_u128, |product, race| {
+        product
+            .checked_mul(ways_to_win(race))
+            .ok_or_else(|| "Product of the ways to win is too large".to_owned())
+    })
 }
 
-fn ways_to_win((time, record): &(i64, i64)) -> i64 {
-    (0..=*time)
-        .map(|wait| (time - wait) * wait)
-        .filter(|d| record < d)
-        .count() as i64
+/// Waiting `w` out of `time` ms travels `w * (time - w)`, which grows up to `w = time / 2` and is
+/// symmetrical around it. So once the lowest winning wait is known, every wait up to
+/// `time - lowest` wins as well.
+fn ways_to_win(race: &(u128, u128)) -> u128 {
+    if race.0 < 1 << 64 {
+        ways_to_win_by_root(race)
+    } else {
+        ways_to_win_by_bisection(race)
+    }
+}
+
+/**
+We win when `w^2 - time * w + record < 0`, which holds strictly between the roots
+`(time ± sqrt(time^2 - 4 * record)) / 2`. The lowest winning wait is the first whole number above
+the lower root. If the square root is exact, the lower root itself only ties the record, which
+doesn't count as a win. Otherwise the root lies between the integer square root and the next
+number, which decides how to round. Needs `time^2` to fit, so `time` must be below 2^64.
+*/
+fn ways_to_win_by_root((time, record): &(u128, u128)) -> u128 {
+    let time_squared = time * time;
+    let discriminant = match record.checked_mul(4) {
+        Some(four_record) if four_record < time_squared => time_squared - four_record,
+        _ => return 0,
+    };
+    let root = isqrt(discriminant);
+    let lowest = if root * root == discriminant {
+        (time - root) / 2 + 1
+    } else {
+        (time - root - 1) / 2 + 1
+    };
+    (time + 1).saturating_sub(2 * lowest)
 }
 
-fn read_amended(input: &str) -> Result<(i64, i64), String> {
+/// Bisects the first half of the waits for the lowest winning wait, for times whose square doesn't
+/// fit in u128. Takes at most 128 steps. A distance that overflows u128 beats any record, and a
+/// distance equal to the record only ties it.
+fn ways_to_win_by_bisection((time, record): &(u128, u128)) -> u128 {
+    let wins = |wait: u128| wait.checked_mul(time - wait).is_none_or(|d| record < &d);
+    // Waiting 0 ms never wins, so `losing` and `winning` bracket the lowest winning wait.
+    let (mut losing, mut winning) = (0, time / 2);
+    if !wins(winning) {
+        return 0;
+    }
+    while winning - losing > 1 {
+        let wait = losing + (winning - losing) / 2;
+        if wins(wait) {
+            winning = wait;
+        } else {
+            losing = wait;
+        }
+    }
+    time - 2 * winning + 1
+}
+
+fn read_amended(input: &str) -> Result<(u128, u128), String> {
     let x = input
         .replace(" ", "")
         .lines()
-        .map(|line| line.split_once(":").map(|(_, v)| read::<i64>(v)).unwrap())
-        .collect::<Result<Vec<i64>, String>>()?;
+        .map(|line| line.split_once(":").map(|(_, v)| read::<u128>(v)).unwrap())
+        .collect::<Result<Vec<u128>, String>>()?;
     match &x[..] {
         [time, distance] => Ok((*time, *distance)),
         _ => Err("Problem reading input".to_owned()),
     }
 }
 
-fn read_races(input: &str) -> Result<Vec<(i64, i64)>, String> {
+fn read_races(input: &str) -> Result<Vec<(u128, u128)>, String> {
     let times_distances = input
         .lines()
         .map(read_line)
@@ -44,8 +98,8 @@ fn read_races(input: &str) -> Result<Vec<(i64, i64)>, String> {
     }
 }
 
-fn read_line(input: &str) -> Result<Vec<i64>, String> {
-    input.split_whitespace().skip(1).map(read::<i64>).collect()
+fn read_line(input: &str) -> Result<Vec<u128>, String> {
+    input.split_whitespace().skip(1).map(read::<u128>).collect()
 }
 
 #[cfg(test)]
@@ -55,13 +109,80 @@ mod tests {
     const SAMPLE_INPUT: &str = "Time:      7  15   30
 Distance:  9  40  200";
 
+    fn ways_to_win_brute_force((time, record): &(u128, u128)) -> u128 {
+        (0..=*time)
+            .map(|wait| (time - wait) * wait)
+            .filter(|d| record < d)
+            .count() as u128
+    }
+
     #[test]
     fn part1_test() {
-        assert_eq!(288, part1(&read_races(SAMPLE_INPUT).unwrap()));
+        assert_eq!(288, part1(&read_races(SAMPLE_INPUT).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn part1_overflow_test() {
+        assert!(part1(&[(u128::MAX, 0), (u128::MAX, 0)]).is_err());
     }
 
     #[test]
     fn part2_test() {
         assert_eq!(71503, ways_to_win(&read_amended(SAMPLE_INPUT).unwrap()));
     }
+
+    #[test]
+    fn ways_to_win_test() {
+        for time in 0..60 {
+            for record in 0..(time * time / 4 + 2) {
+                let expected = ways_to_win_brute_force(&(time, record));
+                assert_eq!(
+                    expected,
+                    ways_to_win_by_root(&(time, record)),
+                    "time {time}, record {record}"
+                );
+                assert_eq!(
+                    expected,
+                    ways_to_win_by_bisection(&(time, record)),
+                    "time {time}, record {record}"
+                );
+            }
+        }
+        // Far beyond i64, with exact roots: only waiting half the time beats the record, and
+        // only ties it when the record is one higher.
+        let time = 2 * 10_u128.pow(18);
+        assert_eq!(1, ways_to_win(&(time, time * time / 4 - 1)));
+        assert_eq!(0, ways_to_win(&(time, time * time / 4)));
+        // Times whose square doesn't fit in u128: waiting w ms ties a record of w * (time - w).
+        let (time, wait) = (10_u128.pow(30) + 1, 10_u128.pow(8));
+        assert_eq!(
+            time - 2 * wait - 1,
+            ways_to_win(&(time, wait * (time - wait)))
+        );
+        assert_eq!(
+            time - 2 * wait + 1,
+            ways_to_win(&(time, wait * (time - wait) - 1))
+        );
+        assert_eq!(u128::MAX - 1, ways_to_win(&(u128::MAX, 0)));
+        assert_eq!(u128::MAX - 3, ways_to_win(&(u128::MAX, u128::MAX - 1)));
+        assert_eq!(u128::MAX - 3, ways_to_win(&(u128::MAX, u128::MAX)));
+    }
+
+    #[test]
+    fn root_matches_bisection_test() {
+        // The largest times the root can handle, with records that are tied exactly and ones
+        // that are close to it
+        for time in [(1 << 64) - 1, (1 << 64) - 2, 1 << 63] {
+            for wait in [1, 1 << 20, (1 << 32) + 7, time / 2] {
+                let tie = wait * (time - wait);
+                for record in [tie - 1, tie, tie + 1, 0] {
+                    assert_eq!(
+                        ways_to_win_by_bisection(&(time, record)),
+                        ways_to_win_by_root(&(time, record)),
+                        "time {time}, record {record}"
+                    );
+                }
+            }
+        }
+    }
 }
@@ -1,8 +1,6 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
 
-use crate::util::read;
+use crate::{options::Options, util::read};
 
 struct Hand {
     cards: Vec<char>,
@@ -22,92 +20,240 @@ impl Ord for HandSorting {
     }
 }
 
-pub fn run(input: &str) -> Result<(), String> {
-    let hands = read_input(input)?;
-    let part1 = part1(&hands);
-    println!("Part 1: {part1}");
-    let part2 = part2(&hands);
-    println!("Part 2: {part2}");
-    Ok(())
+/// A type of hand, identified by how many times each label occurs in it, from most to least
+/// common (e.g. `[3, 2]` for a full house).
+struct HandType {
+    name: String,
+    counts: Vec<usize>,
 }
 
-fn part1(hands: &Vec<Hand>) -> i64 {
-    let card_values: HashMap<char, i64> =
-        "23456789TJQKA".chars().zip(0..).collect::<HashMap<_, _>>();
-    winnings(hands, hand_value_p1, &card_values)
+/// How a hand is scored: the type it makes, which labels its wildcards stand in for, and how it
+/// ranks among the other hands.
+struct ScoredHand<'a> {
+    hand: &'a Hand,
+    hand_type: usize,
+    substitutes: Vec<char>,
+    sorting: HandSorting,
+    rank: i64,
 }
 
-fn part2(hands: &Vec<Hand>) -> i64 {
-    let card_values: HashMap<char, i64> =
-        "J23456789TQKA".chars().zip(0..).collect::<HashMap<_, _>>();
-    winnings(hands, hand_value_p2, &card_values)
+/// The rules of a game of Camel Cards.
+struct Rules {
+    /// Card labels from weakest to strongest, used to break ties between hands of the same type.
+    ranking: Vec<char>,
+    /// Labels that act like whatever label makes the hand strongest.
+    wildcards: Vec<char>,
+    hand_size: usize,
+    /// Hand types from weakest to strongest.
+    hand_types: Vec<HandType>,
 }
+impl Rules {
+    /// Rules with the usual hand types for the hand size: hands with larger groups of cards are
+    /// stronger, i.e. the types are ordered by their counts.
+    fn new(ranking: &str, wildcards: &str, hand_size: usize) -> Result<Rules, String> {
+        let hand_types = partitions(hand_size, hand_size)
+            .into_iter()
+            .sorted()
+            .map(|counts| HandType {
+                name: hand_type_name(&counts),
+                counts,
+            })
+            .collect_vec();
+        Rules::with_hand_types(ranking, wildcards, hand_size, hand_types)
+    }
 
-fn winnings(
-    hands: &Vec<Hand>,
-    hand_value: impl Fn(&Vec<char>) -> i64,
-    card_values: &HashMap<char, i64>,
-) -> i64 {
-    hands
-        .iter()
-        .map(|h| {
-            let sorting = HandSorting {
-                value: hand_value(&h.cards),
-                tie_break_value: sort_value(&h.cards, card_values),
-            };
-            (h, sorting)
+    /// Rules with a custom table of hand types, from weakest to strongest. Every type must be a
+    /// different way of splitting up the hand, with its counts from most to least common. Hands
+    /// that don't make any of the types can't be scored.
+    fn with_hand_types(
+        ranking: &str,
+        wildcards: &str,
+        hand_size: usize,
+        hand_types: Vec<HandType>,
+    ) -> Result<Rules, String> {
+        for hand_type in &hand_types {
+            let HandType { name, counts } = hand_type;
+            if counts.iter().sum::<usize>() != hand_size || counts.contains(&0) {
+                return Err(format!(
+                    "Hand type {name} is not a hand of {hand_size} cards"
+                ));
+            }
+            if !counts.is_sorted_by(|a, b| a >= b) {
+                return Err(format!("The counts of hand type {name} are not in order"));
+            }
+        }
+        if let Some(duplicate) = hand_types.iter().duplicates_by(|t| &t.counts).next() {
+            return Err(format!("Hand type {} is listed twice", duplicate.name));
+        }
+        Ok(Rules {
+            ranking: ranking.chars().collect(),
+            wildcards: wildcards.chars().collect(),
+            hand_size,
+            hand_types,
         })
-        .sorted_by_key(|(_, s)| *s)
-        .zip(1..)
-        .map(|((h, _), i)| h.rank * i)
-        .sum()
+    }
+
+    fn part1() -> Result<Rules, String> {
+        Rules::new("23456789TJQKA", "", 5)
+    }
+
+    fn part2() -> Result<Rules, String> {
+        Rules::new("J23456789TQKA", "J", 5)
+    }
+
+    fn card_value(&self, card: &char) -> Result<i64, String> {
+        self.ranking
+            .iter()
+            .position(|c| c == card)
+            .map(|p| p as i64)
+            .ok_or_else(|| format!("Unknown card {card}"))
+    }
+
+    fn tie_break_value(&self, cards: &[char]) -> Result<i64, String> {
+        // interpret the cards as a number in base `ranking.len()`, using the card values as digits
+        cards.iter().try_fold(0, |acc, c| {
+            Ok(acc * self.ranking.len() as i64 + self.card_value(c)?)
+        })
+    }
+
+    /// Determines the index of the strongest hand type the cards can make, and the labels the
+    /// wildcards (if any) take on to make it. Every way of replacing the wildcards with the other
+    /// labels is tried, so that any table of hand types is scored correctly.
+    fn classify(&self, cards: &[char]) -> Result<(usize, Vec<char>), String> {
+        if cards.len() != self.hand_size {
+            return Err(format!(
+                "Hand {} does not have {} cards",
+                String::from_iter(cards),
+                self.hand_size
+            ));
+        }
+        let (wild, natural): (Vec<char>, Vec<char>) =
+            cards.iter().partition(|c| self.wildcards.contains(c));
+        let labels = match self
+            .ranking
+            .iter()
+            .filter(|c| !self.wildcards.contains(c))
+            .collect_vec()
+        {
+            labels if labels.is_empty() => self.ranking.iter().collect_vec(),
+            labels => labels,
+        };
+        // The order of the substitutes doesn't change the counts, so each multiset is tried once.
+        labels
+            .into_iter()
+            .combinations_with_replacement(wild.len())
+            .filter_map(|substitutes| {
+                let counts = card_counts(natural.iter().chain(substitutes.iter().copied()));
+                let hand_type = self.hand_types.iter().position(|t| t.counts == counts)?;
+                Some((hand_type, substitutes.into_iter().copied().collect_vec()))
+            })
+            .max_by_key(|(hand_type, _)| *hand_type)
+            .ok_or_else(|| format!("Hand {} has no type", String::from_iter(cards)))
+    }
 }
 
-fn sort_value(cards: &Vec<char>, card_values: &HashMap<char, i64>) -> i64 {
-    // interpret the cards as a base 13 number based on a (provided) 0..13 mapping of their labels
-    cards.iter().fold(0, |acc, c| acc * 13 + card_values[c])
+/// All ways to write n as a sum of numbers no larger than max, largest numbers first.
+fn partitions(n: usize, max: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    (1..=max.min(n))
+        .flat_map(|first| {
+            partitions(n - first, first)
+                .into_iter()
+                .map(move |mut rest| {
+                    rest.insert(0, first);
+                    rest
+                })
+        })
+        .collect()
 }
 
-fn hand_value_p1(cards: &Vec<char>) -> i64 {
-    hand_value(&card_counts(cards))
+fn hand_type_name(counts: &[usize]) -> String {
+    match counts {
+        [5] => "five of a kind".to_owned(),
+        [4, 1] => "four of a kind".to_owned(),
+        [3, 2] => "full house".to_owned(),
+        [3, 1, 1] => "three of a kind".to_owned(),
+        [2, 2, 1] => "two pair".to_owned(),
+        [2, 1, 1, 1] => "one pair".to_owned(),
+        [1, 1, 1, 1, 1] => "high card".to_owned(),
+        _ => counts.iter().join("+"),
+    }
 }
 
-fn hand_value(counts: &Vec<i64>) -> i64 {
-    match &counts[..] {
-        [5] => 6,          // five of a kind
-        [1, 4] => 5,       // four of a kind
-        [2, 3] => 4,       // full house
-        [1, 1, 3] => 3,    // three of a kind
-        [1, 2, 2] => 2,    // two pair
-        [1, 1, 1, 2] => 1, // one pair
-        _ => 0,            // high card
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
+    let hands = read_input(input)?;
+    for (part, rules) in [(1, Rules::part1()?), (2, Rules::part2()?)] {
+        let scored = score(&hands, &rules)?;
+        if options.show {
+            println!("{}", explain(&scored, &rules));
+        }
+        println!("Part {part}: {}", winnings(&scored));
     }
+    Ok(())
 }
 
-fn card_counts(cards: &Vec<char>) -> Vec<i64> {
-    // count the cards by label to determine what type the hand is
-    cards
+/// Scores the hands, ordered from weakest to strongest.
+fn score<'a>(hands: &'a [Hand], rules: &Rules) -> Result<Vec<ScoredHand<'a>>, String> {
+    let classified = hands
+        .iter()
+        .map(|hand| {
+            let (hand_type, substitutes) = rules.classify(&hand.cards)?;
+            let sorting = HandSorting {
+                value: hand_type as i64,
+                tie_break_value: rules.tie_break_value(&hand.cards)?,
+            };
+            Ok((hand, hand_type, substitutes, sorting))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(classified
         .into_iter()
-        .fold(HashMap::new(), |mut acc, card| {
-            *acc.entry(card).or_insert(0) += 1;
-            acc
+        .sorted_by_key(|(_, _, _, sorting)| *sorting)
+        .zip(1..)
+        .map(
+            |((hand, hand_type, substitutes, sorting), rank)| ScoredHand {
+                hand,
+                hand_type,
+                substitutes,
+                sorting,
+                rank,
+            },
+        )
+        .collect())
+}
+
+fn winnings(scored: &[ScoredHand]) -> i64 {
+    scored.iter().map(|s| s.hand.rank * s.rank).sum()
+}
+
+/// Lists every hand with how it was scored, from weakest to strongest.
+fn explain(scored: &[ScoredHand], rules: &Rules) -> String {
+    scored
+        .iter()
+        .map(|s| {
+            let cards = String::from_iter(&s.hand.cards);
+            let hand_type = &rules.hand_types[s.hand_type].name;
+            let substitution = match &s.substitutes[..] {
+                [] => String::new(),
+                substitutes => format!(" (wildcards as {})", substitutes.iter().dedup().join(", ")),
+            };
+            format!(
+                "{cards} {:<34} tie-break {:>8}  rank {:>4}: {} * {} = {}",
+                format!("{hand_type}{substitution}"),
+                s.sorting.tie_break_value,
+                s.rank,
+                s.hand.rank,
+                s.rank,
+                s.hand.rank * s.rank
+            )
         })
-        .values()
-        // sort the counts so the match can be simpler
-        .sorted()
-        .cloned()
-        .collect_vec()
+        .join("\n")
 }
 
-fn hand_value_p2(cards: &Vec<char>) -> i64 {
-    let (jokers, others) = cards.iter().partition::<Vec<char>, _>(|&c| *c == 'J');
-    if others.is_empty() {
-        return hand_value(&vec![jokers.len() as i64]);
-    }
-    let mut counts = card_counts(&others);
-    let most_common = counts.len() - 1;
-    counts[most_common] += jokers.len() as i64;
-    hand_value(&counts)
+/// Counts the cards by label, from most to least common, to determine what type the hand is.
+fn card_counts<'a>(cards: impl Iterator<Item = &'a char>) -> Vec<usize> {
+    cards.counts().into_values().sorted().rev().collect_vec()
 }
 
 fn read_input(input: &str) -> Result<Vec<Hand>, String> {
@@ -134,19 +280,122 @@ KK677 28
 KTJJT 220
 QQQJA 483";
 
+    fn hand_type(rules: &Rules, hand: &str) -> Result<usize, String> {
+        rules
+            .classify(&hand.chars().collect_vec())
+            .map(|(hand_type, _)| hand_type)
+    }
+
     #[test]
     fn hand_value_test() {
-        assert_eq!(6, hand_value_p1(&"KKKKK".chars().collect()));
-        assert_eq!(4, hand_value_p1(&"KKQQK".chars().collect()));
+        assert_eq!(6, hand_type(&Rules::part1().unwrap(), "KKKKK").unwrap());
+        assert_eq!(4, hand_type(&Rules::part1().unwrap(), "KKQQK").unwrap());
+    }
+
+    #[test]
+    fn hand_type_wildcard_test() {
+        let rules = Rules::part2().unwrap();
+        assert_eq!(5, hand_type(&rules, "KTJJT").unwrap());
+        assert_eq!(6, hand_type(&rules, "JJJJJ").unwrap());
+        assert_eq!(1, hand_type(&rules, "2345J").unwrap());
+        assert!(hand_type(&rules, "2345").is_err());
     }
 
     #[test]
     fn part1_test() {
-        assert_eq!(6440, part1(&read_input(SAMPLE_INPUT).unwrap()));
+        let hands = read_input(SAMPLE_INPUT).unwrap();
+        assert_eq!(
+            6440,
+            winnings(&score(&hands, &Rules::part1().unwrap()).unwrap())
+        );
     }
 
     #[test]
     fn part2_test() {
-        assert_eq!(5905, part2(&read_input(SAMPLE_INPUT).unwrap()));
+        let hands = read_input(SAMPLE_INPUT).unwrap();
+        assert_eq!(
+            5905,
+            winnings(&score(&hands, &Rules::part2().unwrap()).unwrap())
+        );
+    }
+
+    #[test]
+    fn explain_test() {
+        let hands = read_input(SAMPLE_INPUT).unwrap();
+        let rules = Rules::part2().unwrap();
+        let explanation = explain(&score(&hands, &rules).unwrap(), &rules);
+        assert_eq!(
+            Some("KTJJT four of a kind (wildcards as T)    tie-break   333953  rank    5: 220 * 5 = 1100"),
+            explanation.lines().last()
+        );
+        assert!(explanation.starts_with("32T3K one pair "));
+    }
+
+    #[test]
+    fn variant_test() {
+        // Six cards, with both jokers and twos wild
+        let rules = Rules::new("2J3456789TQKA", "J2", 6).unwrap();
+        assert_eq!(11, rules.hand_types.len());
+        assert_eq!("6", rules.hand_types[10].name);
+        let four_two = hand_type(&rules, "KK2QQJ").unwrap();
+        assert_eq!(vec![4, 2], rules.hand_types[four_two].counts);
+        let hands = read_input("KK2QQJ 10\nAAKQT9 20\nJJ2345 30").unwrap();
+        // JJ2345 has three wildcards, but only makes four of a kind
+        assert_eq!(
+            20 + 30 * 2 + 10 * 3,
+            winnings(&score(&hands, &rules).unwrap())
+        );
+    }
+
+    #[test]
+    fn custom_hand_types_test() {
+        // Two pair beats three of a kind, so two wildcards are better off as different labels
+        let hand_types = [
+            vec![1, 1, 1, 1, 1],
+            vec![2, 1, 1, 1],
+            vec![3, 1, 1],
+            vec![2, 2, 1],
+            vec![3, 2],
+            vec![4, 1],
+            vec![5],
+        ]
+        .into_iter()
+        .map(|counts| HandType {
+            name: hand_type_name(&counts),
+            counts,
+        })
+        .collect_vec();
+        let rules = Rules::with_hand_types("J23456789TQKA", "J", 5, hand_types).unwrap();
+        let (two_pair, substitutes) = rules.classify(&"KQ2JJ".chars().collect_vec()).unwrap();
+        assert_eq!("two pair", rules.hand_types[two_pair].name);
+        assert_eq!(2, substitutes.iter().unique().count());
+        assert_eq!(1, hand_type(&rules, "KQ23J").unwrap());
+    }
+
+    #[test]
+    fn invalid_hand_types_test() {
+        let rules = |counts: Vec<Vec<usize>>| {
+            let hand_types = counts
+                .into_iter()
+                .map(|counts| HandType {
+                    name: counts.iter().join("+"),
+                    counts,
+                })
+                .collect_vec();
+            Rules::with_hand_types("23456789TJQKA", "", 3, hand_types).map(|_| ())
+        };
+        assert_eq!(Ok(()), rules(vec![vec![1, 1, 1], vec![2, 1], vec![3]]));
+        assert_eq!(
+            Err("Hand type 2+2 is not a hand of 3 cards".to_owned()),
+            rules(vec![vec![2, 2]])
+        );
+        assert_eq!(
+            Err("The counts of hand type 1+2 are not in order".to_owned()),
+            rules(vec![vec![1, 2]])
+        );
+        assert_eq!(
+            Err("Hand type 3 is listed twice".to_owned()),
+            rules(vec![vec![3], vec![2, 1], vec![3]])
+        );
     }
 }
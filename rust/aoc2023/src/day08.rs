@@ -3,14 +3,61 @@ use std::collections::HashMap;
 use itertools::Itertools;
 use regex::Regex;
 
-use crate::util::lcm;
+use crate::{
+    options::Options,
+    util::math::{checked_lcm, crt},
+};
 
-pub fn run(input: &str) -> Result<(), String> {
+/// The path of a ghost. Where a ghost goes next only depends on its node and its position in the
+/// instructions, so every path ends up in a cycle.
+#[derive(Debug, PartialEq)]
+struct GhostPath {
+    /// Number of steps before the cycle starts.
+    prefix: i64,
+    /// Number of steps in the cycle.
+    cycle: i64,
+    /// Steps after which the ghost is on an end node, up to and including the first round of the
+    /// cycle (i.e. step `prefix + cycle`). After that, the steps repeat every `cycle` steps.
+    ends: Vec<i64>,
+}
+impl GhostPath {
+    fn is_at_end(&self, step: i64) -> bool {
+        let last = self.prefix + self.cycle;
+        let step = if step > last {
+            step - (step - last - 1 + self.cycle) / self.cycle * self.cycle
+        } else {
+            step
+        };
+        self.ends.contains(&step)
+    }
+
+    /// Whether the ghost is on an end node exactly at the multiples of its first arrival, which is
+    /// what taking the LCM of the first arrivals assumes.
+    fn ends_at_multiples(&self) -> bool {
+        match self.ends.first() {
+            Some(&first) => {
+                self.cycle % first == 0
+                    && (1..=self.prefix + self.cycle)
+                        .filter(|step| step % first == 0)
+                        .eq(self.ends.iter().copied())
+            }
+            None => false,
+        }
+    }
+}
+
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let (instr, map) = read_input(input)?;
     let part1 = part1(&instr, &map);
     println!("Part 1: {part1}");
-    let part2 = part2(&instr, &map);
+    let paths = ghost_paths(&instr, &map)?;
+    let part2 = part2(&paths)?;
     println!("Part 2: {part2}");
+    if lcm_shortcut_applies(&paths) {
+        println!("Every ghost ends at multiples of its first arrival, so the LCM shortcut applies");
+    } else {
+        println!("The LCM shortcut does not apply to these ghosts");
+    }
     Ok(())
 }
 
@@ -18,17 +65,98 @@ fn part1(instr: &Vec<char>, map: &HashMap<&str, (&str, &str)>) -> i64 {
     follow_map("AAA", &instr, |pos| "ZZZ".eq(pos), &map)
 }
 
-fn part2(instr: &Vec<char>, map: &HashMap<&str, (&str, &str)>) -> i64 {
-    let start = map
-        .keys()
-        .filter(|p| p.ends_with('A'))
-        .cloned()
-        .collect_vec();
-    start
+/// Finds the first step after which all ghosts are on an end node at the same time.
+fn part2(paths: &[GhostPath]) -> Result<i64, String> {
+    if paths.is_empty() {
+        return Err("There are no ghosts".to_owned());
+    }
+    if lcm_shortcut_applies(paths) {
+        paths
+            .iter()
+            .map(|p| p.ends[0])
+            .try_fold(1, checked_lcm)
+            .ok_or_else(|| "The LCM of the first arrivals is too large".to_owned())
+    } else {
+        first_common_end(paths)
+    }
+}
+
+fn lcm_shortcut_applies(paths: &[GhostPath]) -> bool {
+    paths.iter().all(GhostPath::ends_at_multiples)
+}
+
+fn first_common_end(paths: &[GhostPath]) -> Result<i64, String> {
+    // Before the longest prefix, the ghost with that prefix can only end on its own ends.
+    let Some(longest) = paths.iter().max_by_key(|p| p.prefix) else {
+        return Err("There are no ghosts".to_owned());
+    };
+    if let Some(step) = longest
+        .ends
         .iter()
-        .map(|from| follow_map(from, instr, |p| p.ends_with('Z'), map))
-        .reduce(lcm)
-        .unwrap()
+        .take_while(|step| **step <= longest.prefix)
+        .find(|step| paths.iter().all(|p| p.is_at_end(**step)))
+    {
+        return Ok(*step);
+    }
+    // After it, every ghost is in its cycle, which gives a system of congruences per combination
+    // of end steps in the cycles.
+    let congruences = paths.iter().try_fold(vec![(0, 1)], |congruences, path| {
+        congruences
+            .iter()
+            .cartesian_product(path.ends.iter().filter(|step| **step > path.prefix))
+            .filter_map(|(&congruence, &step)| {
+                crt(congruence, (step.into(), path.cycle.into())).transpose()
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map(|congruences| congruences.into_iter().unique().collect_vec())
+    })?;
+    let after = longest.prefix as i128 + 1;
+    congruences
+        .iter()
+        .map(|(residue, modulus)| after + (residue - after).rem_euclid(*modulus))
+        .min()
+        .ok_or_else(|| "The ghosts are never all on an end node at once".to_owned())
+        .and_then(|step| i64::try_from(step).map_err(|_| format!("Step {step} is too large")))
+}
+
+fn ghost_paths(
+    instr: &[char],
+    map: &HashMap<&str, (&str, &str)>,
+) -> Result<Vec<GhostPath>, String> {
+    map.keys()
+        .filter(|p| p.ends_with('A'))
+        .sorted()
+        .map(|from| ghost_path(from, instr, map))
+        .collect()
+}
+
+/// Follows a ghost until it is on the same node at the same position in the instructions again.
+fn ghost_path(
+    from: &str,
+    instr: &[char],
+    map: &HashMap<&str, (&str, &str)>,
+) -> Result<GhostPath, String> {
+    if instr.is_empty() {
+        return Err("There are no instructions".to_owned());
+    }
+    let mut seen = HashMap::from([((from, 0), 0)]);
+    let mut ends = Vec::new();
+    let mut node = from;
+    for step in 1.. {
+        let next = &instr[(step - 1) % instr.len()];
+        node = next_path(node, next, map).ok_or_else(|| format!("Cannot go {next} from {node}"))?;
+        if node.ends_with('Z') {
+            ends.push(step as i64);
+        }
+        if let Some(first) = seen.insert((node, step % instr.len()), step) {
+            return Ok(GhostPath {
+                prefix: first as i64,
+                cycle: (step - first) as i64,
+                ends,
+            });
+        }
+    }
+    unreachable!()
 }
 
 fn follow_map(
@@ -127,6 +255,61 @@ XXX = (XXX, XXX)";
     #[test]
     fn part2_test() {
         let (instr, map) = read_input(SAMPLE_INPUT_2).unwrap();
-        assert_eq!(6, part2(&instr, &map));
+        assert_eq!(6, part2(&ghost_paths(&instr, &map).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn ghost_paths_test() {
+        let (instr, map) = read_input(SAMPLE_INPUT_2).unwrap();
+        let paths = ghost_paths(&instr, &map).unwrap();
+        assert_eq!(
+            vec![
+                GhostPath {
+                    prefix: 1,
+                    cycle: 2,
+                    ends: vec![2]
+                },
+                GhostPath {
+                    prefix: 1,
+                    cycle: 6,
+                    ends: vec![3, 6]
+                }
+            ],
+            paths
+        );
+        assert!(lcm_shortcut_applies(&paths));
+        assert_eq!(Ok(6), first_common_end(&paths));
+        assert!(paths[1].is_at_end(9));
+        assert!(!paths[1].is_at_end(10));
+    }
+
+    #[test]
+    fn part2_without_lcm_shortcut_test() {
+        // 11A ends after 2, 5, 8, ... steps and 22A after 1, 3, 5, ...
+        let (instr, map) = read_input(
+            "\
+L
+
+11A = (11B, XXX)
+11B = (11Z, XXX)
+11Z = (11C, XXX)
+11C = (11B, XXX)
+22A = (22Z, XXX)
+22Z = (22A, XXX)
+XXX = (XXX, XXX)",
+        )
+        .unwrap();
+        let paths = ghost_paths(&instr, &map).unwrap();
+        assert!(!lcm_shortcut_applies(&paths));
+        assert_eq!(5, part2(&paths).unwrap());
+
+        let (instr, map) =
+            read_input("L\n\n11A = (11Z, XXX)\n11Z = (XXX, XXX)\nXXX = (XXX, XXX)").unwrap();
+        assert_eq!(1, part2(&ghost_paths(&instr, &map).unwrap()).unwrap());
+        let (instr, map) = read_input(
+            "L\n\n11A = (11Z, XXX)\n11Z = (11Z, XXX)\n22A = (22B, XXX)\n22B = (22B, XXX)",
+        )
+        .unwrap();
+        assert!(part2(&ghost_paths(&instr, &map).unwrap()).is_err());
     }
 }
@@ -1,48 +1,110 @@
 use itertools::Itertools;
 
-use crate::util::read_all;
+use crate::{options::Options, util::read_all};
 
-pub fn run(input: &str) -> Result<(), String> {
-    let readings = read_input(input)?;
-    let part1 = part1(&readings);
-    println!("Part 1: {part1}");
-    let part2 = part2(&readings);
-    println!("Part 2: {part2}");
-    Ok(())
+/**
+The polynomial that produces a sequence, in Newton form: the value at index `x` is the sum of
+`coefficients[k] * binomial(x, k)`. The coefficients are the first values of the successive rows of
+differences, so they are exact integers, and evaluating them works for any index, including
+negative ones before the start of the sequence.
+*/
+#[derive(Debug, PartialEq)]
+struct Polynomial {
+    coefficients: Vec<i128>,
+    /// The length of the sequence it was fitted to.
+    len: i128,
 }
+impl Polynomial {
+    /// Fits the polynomial by taking differences until they are all zero.
+    fn fit(sequence: &[i128]) -> Result<Polynomial, String> {
+        let mut coefficients = Vec::new();
+        let mut row = sequence.to_vec();
+        while row.iter().any(|&v| v != 0) {
+            if row.len() == 1 {
+                return Err(format!(
+                    "Differences of {} never become all zero",
+                    sequence.iter().join(" ")
+                ));
+            }
+            coefficients.push(row[0]);
+            row = row
+                .iter()
+                .map_windows(|[&a, &b]| b.checked_sub(a))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| format!("Differences of {} overflow", sequence.iter().join(" ")))?;
+        }
+        if row.is_empty() {
+            return Err("Cannot fit an empty sequence".to_owned());
+        }
+        Ok(Polynomial {
+            coefficients,
+            len: sequence.len() as i128,
+        })
+    }
 
-fn read_input(input: &str) -> Result<Vec<Vec<i64>>, String> {
-    input.lines().map(read_all::<i64>).try_collect()
-}
+    fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    fn at(&self, x: i128) -> Result<i128, String> {
+        let overflow = || format!("Value at {x} overflows");
+        let mut binomial = 1i128;
+        let mut value = 0i128;
+        for (k, coefficient) in self.coefficients.iter().enumerate() {
+            if k > 0 {
+                // binomial(x, k) = binomial(x, k - 1) * (x - k + 1) / k, which divides exactly
+                let k = k as i128;
+                binomial = binomial.checked_mul(x - k + 1).ok_or_else(overflow)? / k;
+            }
+            value = coefficient
+                .checked_mul(binomial)
+                .and_then(|term| value.checked_add(term))
+                .ok_or_else(overflow)?;
+        }
+        Ok(value)
+    }
 
-fn part1(readings: &Vec<Vec<i64>>) -> i64 {
-    readings.iter().map(determine_next).sum()
+    /// The value `steps` steps after the end of the sequence.
+    fn after(&self, steps: i128) -> Result<i128, String> {
+        self.at(self.len - 1 + steps)
+    }
+
+    /// The value `steps` steps before the start of the sequence.
+    fn before(&self, steps: i128) -> Result<i128, String> {
+        self.at(-steps)
+    }
 }
 
-fn determine_next(reading: &Vec<i64>) -> i64 {
-    iterate_differences(&reading, |mut v| v.pop().unwrap()).sum()
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
+    let readings = read_input(input)?;
+    if options.show {
+        for reading in &readings {
+            let polynomial = Polynomial::fit(reading)?;
+            println!(
+                "{}: degree {}, Newton coefficients {}",
+                reading.iter().join(" "),
+                polynomial.degree(),
+                polynomial.coefficients.iter().join(" ")
+            );
+        }
+    }
+    let part1 = part1(&readings)?;
+    println!("Part 1: {part1}");
+    let part2 = part2(&readings)?;
+    println!("Part 2: {part2}");
+    Ok(())
 }
 
-fn iterate_differences(
-    reading: &Vec<i64>,
-    extract: impl Fn(Vec<i64>) -> i64,
-) -> impl Iterator<Item = i64> {
-    std::iter::successors(Some(reading.clone()), |prev| {
-        Some(prev.iter().map_windows(|[&a, &b]| b - a).collect_vec())
-    })
-    .take_while(|v| v.iter().any(|&i| i != 0))
-    .map(extract)
+fn read_input(input: &str) -> Result<Vec<Vec<i128>>, String> {
+    input.lines().map(read_all::<i128>).try_collect()
 }
 
-fn part2(readings: &Vec<Vec<i64>>) -> i64 {
-    readings.iter().map(determine_prev).sum()
+fn part1(readings: &[Vec<i128>]) -> Result<i128, String> {
+    readings.iter().map(|r| Polynomial::fit(r)?.after(1)).sum()
 }
 
-fn determine_prev(reading: &Vec<i64>) -> i64 {
-    iterate_differences(&reading, |v| v[0])
-        .collect_vec()
-        .iter()
-        .rfold(0, |acc, next| next - acc)
+fn part2(readings: &[Vec<i128>]) -> Result<i128, String> {
+    readings.iter().map(|r| Polynomial::fit(r)?.before(1)).sum()
 }
 
 #[cfg(test)]
@@ -57,12 +119,37 @@ mod tests {
     #[test]
     fn part1_test() {
         let readings: Vec<_> = read_input(SAMPLE_INPUT).unwrap();
-        assert_eq!(114, part1(&readings));
+        assert_eq!(Ok(114), part1(&readings));
     }
 
     #[test]
     fn part2_test() {
         let readings: Vec<_> = read_input(SAMPLE_INPUT).unwrap();
-        assert_eq!(2, part2(&readings));
+        assert_eq!(Ok(2), part2(&readings));
+    }
+
+    #[test]
+    fn fit_test() {
+        let triangular = Polynomial::fit(&[1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(vec![1, 2, 1], triangular.coefficients);
+        assert_eq!(2, triangular.degree());
+        assert_eq!(0, Polynomial::fit(&[0, 0]).unwrap().degree());
+        assert!(Polynomial::fit(&[1, 2, 4, 8]).is_err());
+        assert!(Polynomial::fit(&[]).is_err());
+    }
+
+    #[test]
+    fn extrapolate_test() {
+        let triangular = Polynomial::fit(&[1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(Ok(36), triangular.after(2));
+        assert_eq!(Ok(0), triangular.before(1));
+        assert_eq!(Ok(1), triangular.before(3));
+        // the n-th value is (n + 1)(n + 2) / 2
+        let n = 1_000_000_000_005;
+        assert_eq!(
+            Ok((n + 1) * (n + 2) / 2),
+            triangular.after(1_000_000_000_000)
+        );
+        assert!(triangular.after(i128::MAX / 2).is_err());
     }
 }
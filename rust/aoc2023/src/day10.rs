@@ -1,13 +1,63 @@
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
-use crate::util::{assign_coordinates, Coord, Step};
+use itertools::Itertools;
 
-pub fn run(input: &str) -> Result<(), String> {
-    let (maze, start) = read_input(input)?;
-    let distances = distances(start, &maze);
+use crate::{
+    options::Options,
+    util::{assign_coordinates, Coord},
+};
+
+/// The ways in which the maze can fail to have a single loop through the start.
+#[derive(Debug, PartialEq, Eq)]
+enum PipeError {
+    /// There is no `S` in the maze.
+    NoStart,
+    /// None of the pipe shapes at the start make a closed loop.
+    NoStartPipe(Coord),
+    /// More than one pipe shape at the start makes a closed loop.
+    AmbiguousStart(Coord, Vec<char>),
+    /// The loop reaches a tile that isn't a pipe.
+    NotAPipe(Coord, char),
+    /// The loop runs off the edge of the maze.
+    OffTheMaze(Coord),
+    /// The first pipe leads into the second, which doesn't connect back to it.
+    Disconnected(Coord, Coord),
+}
+impl Display for PipeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoStart => write!(f, "Start not found"),
+            Self::NoStartPipe(c) => write!(f, "No pipe at the start {c} makes a closed loop"),
+            Self::AmbiguousStart(c, pipes) => write!(
+                f,
+                "The start {c} could be any of {} to make a closed loop",
+                pipes.iter().join(", ")
+            ),
+            Self::NotAPipe(c, tile) => write!(f, "The loop reaches {tile} at {c}, not a pipe"),
+            Self::OffTheMaze(c) => write!(f, "The loop runs off the maze at {c}"),
+            Self::Disconnected(from, to) => {
+                write!(
+                    f,
+                    "The pipe at {from} leads to {to}, which does not connect back"
+                )
+            }
+        }
+    }
+}
+
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
+    let (maze, start) = read_input(input).map_err(|e| e.to_string())?;
+    let path = loop_path(start, &maze).map_err(|e| e.to_string())?;
+    let distances = distances(&path);
     let part1 = part1(&distances)?;
     println!("Part 1: {part1}");
-    let part2 = part2(&maze, &distances);
+    if options.show {
+        println!("{}", show_loop(&maze, &distances, &enclosed_tiles(&path)));
+    }
+    let part2 = part2(&path);
     println!("Part 2: {part2}");
     Ok(())
 }
@@ -21,118 +71,177 @@ fn part1(distances: &HashMap<Coord, i64>) -> Result<i64, String> {
 }
 
 /**
-We can consider the loop a convoluted polygon. To find what points are inside the polygon, we can
-use the [ray casting algorithm]: draw a line from "outside" to each point that is not in the loop.
-Count the number of time it crosses a pipe. If the number is odd, the point is inside the loop.
-To simplify, we always cast from x = 0 and ignore loop parts that are '-', '7' and 'F', as we
-assume the ray can "squeeze" past those, i.e., the ray follows the upper part of a tile.
-It also works if you discount '-', 'J' and 'L' as "passable".
-
-It could be made more efficient by choosing the shortest path to "outside", in which case also
-the y direction should be considered. Another optimization would be to determine the smallest
-bounding rectangle to use for "outside" coordinates. I've left these optimizations out since it
-currently runs takes ~50ms in release mode on my machine.
-
-[ray casting algorithm]: https://en.wikipedia.org/wiki/Point_in_polygon#Ray_casting_algorithm
+The loop is a polygon through the centers of its tiles, with its area given by the [shoelace
+formula]. As all corners are on whole coordinates, [Pick's theorem] relates that area to the
+number of whole coordinates inside it and on its boundary: `A = I + B / 2 - 1`. The tiles on the
+boundary are exactly the tiles of the loop, so the enclosed tiles are `I = A - B / 2 + 1`.
+
+[shoelace formula]: https://en.wikipedia.org/wiki/Shoelace_formula
+[Pick's theorem]: https://en.wikipedia.org/wiki/Pick%27s_theorem
 */
-fn part2(maze: &HashMap<Coord, char>, distances: &HashMap<Coord, i64>) -> i64 {
-    let can_pass = HashSet::from(['-', '7', 'F']);
-    let is_inside = |candidate: &Coord| {
-        let crossings = (0..candidate.x)
-            .map(|x| Coord::from(x, candidate.y))
-            .filter(|c| {
-                distances.contains_key(c) && maze.get(c).map(|p| !can_pass.contains(p)).unwrap()
-            })
-            .count();
-        crossings % 2 == 1
-    };
-    maze.keys()
-        .filter(|c| !distances.contains_key(c) && is_inside(c))
-        .count() as i64
-}
-
-fn distances(start: Coord, maze: &HashMap<Coord, char>) -> HashMap<Coord, i64> {
-    // I used Dijkstra's algorithm for this, but it wasn't really necessary in the end.
-    fn all_paths(
-        mut to_visit: BinaryHeap<Step>,
-        mut seen: HashSet<Coord>,
-        maze: &HashMap<Coord, char>,
-        mut result: HashMap<Coord, i64>,
-    ) -> HashMap<Coord, i64> {
-        match to_visit.pop() {
-            None => result,
-            Some(Step { at, distance }) => {
-                result.insert(at, distance);
-                neighbors(&at, maze.get(&at).unwrap())
-                    .iter()
-                    .filter(|&n| seen.insert(*n))
-                    .map(|n| Step {
-                        at: *n,
-                        distance: distance + 1,
-                    })
-                    .for_each(|s| to_visit.push(s));
-                all_paths(to_visit, seen, maze, result)
-            }
+fn part2(path: &[Coord]) -> i64 {
+    (twice_signed_area(path).abs() - path.len() as i64) / 2 + 1
+}
+
+/// Twice the area of the loop by the shoelace formula. With y pointing down, it is positive if the
+/// loop goes around clockwise.
+fn twice_signed_area(path: &[Coord]) -> i64 {
+    path.iter()
+        .chain(path.first())
+        .map_windows(|[a, b]| a.x * b.y - b.x * a.y)
+        .sum()
+}
+
+/// Finds the tiles enclosed by the loop. Walking along the loop, the inside is always on the same
+/// side: the right if it goes around clockwise, the left otherwise. The tiles on that side of every
+/// step are inside, and so is everything reached from them without crossing the loop.
+fn enclosed_tiles(path: &[Coord]) -> HashSet<Coord> {
+    let on_loop = path.iter().collect::<HashSet<_>>();
+    let clockwise = twice_signed_area(path) > 0;
+    let mut to_visit = path
+        .iter()
+        .chain(path.first())
+        .map_windows(|[a, b]| {
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            let inside = if clockwise { (-dy, dx) } else { (dy, -dx) };
+            [a.move_by(inside), b.move_by(inside)]
+        })
+        .flatten()
+        .filter(|c| !on_loop.contains(c))
+        .collect_vec();
+    let mut enclosed = HashSet::new();
+    while let Some(c) = to_visit.pop() {
+        if enclosed.insert(c) {
+            to_visit.extend(c.neighbors4().into_iter().filter(|n| !on_loop.contains(n)));
         }
     }
-    let start_step = Step {
-        at: start,
-        distance: 0,
+    debug_assert_eq!(part2(path), enclosed.len() as i64);
+    enclosed
+}
+
+/// Shows the maze with its pipes as box-drawing characters: the loop is highlighted, the enclosed
+/// tiles are marked and the pipes that are not part of the loop are dimmed.
+fn show_loop(
+    maze: &HashMap<Coord, char>,
+    distances: &HashMap<Coord, i64>,
+    enclosed: &HashSet<Coord>,
+) -> String {
+    let (max_x, max_y) = maze
+        .keys()
+        .fold((0, 0), |(mx, my), Coord { x, y }| (mx.max(*x), my.max(*y)));
+    (0..=max_y)
+        .map(|y| {
+            (0..=max_x)
+                .map(|x| Coord::from(x, y))
+                .map(|c| {
+                    // https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit
+                    let (token, color) = if distances.contains_key(&c) {
+                        (box_drawing(maze[&c]), "1;38;5;226")
+                    } else if enclosed.contains(&c) {
+                        ('•', "38;5;46")
+                    } else {
+                        (maze.get(&c).copied().map_or(' ', box_drawing), "38;5;8")
+                    };
+                    format!("\x1b[{color}m{token}\x1b[0m")
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn box_drawing(pipe: char) -> char {
+    match pipe {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        '.' => '·',
+        _ => pipe,
+    }
+}
+
+/// The distance along the loop from the start to every tile of the loop, going either way.
+fn distances(path: &[Coord]) -> HashMap<Coord, i64> {
+    path.iter()
+        .enumerate()
+        .map(|(i, c)| (*c, i.min(path.len() - i) as i64))
+        .collect()
+}
+
+/// Walks the loop from the start, returning its tiles in order.
+fn loop_path(start: Coord, maze: &HashMap<Coord, char>) -> Result<Vec<Coord>, PipeError> {
+    let start_pipe = maze.get(&start).ok_or(PipeError::OffTheMaze(start))?;
+    walk_loop(start, *start_pipe, maze)
+}
+
+/// Walks the loop as if the start had the given pipe, checking that every pipe connects to the
+/// one before it and that the walk ends up back at the start.
+fn walk_loop(
+    start: Coord,
+    start_pipe: char,
+    maze: &HashMap<Coord, char>,
+) -> Result<Vec<Coord>, PipeError> {
+    let pipe_at = |c: &Coord| match maze.get(c) {
+        _ if *c == start => Ok(start_pipe),
+        Some(pipe) => Ok(*pipe),
+        None => Err(PipeError::OffTheMaze(*c)),
     };
-    all_paths(
-        BinaryHeap::from([start_step]),
-        HashSet::from([start]),
-        maze,
-        HashMap::new(),
-    )
+    let mut path = vec![start];
+    let (mut previous, mut current) = (start, neighbors(&start, &start_pipe)?[0]);
+    while current != start {
+        let [a, b] = neighbors(&current, &pipe_at(&current)?)?;
+        let next = match previous {
+            p if p == a => b,
+            p if p == b => a,
+            _ => return Err(PipeError::Disconnected(previous, current)),
+        };
+        path.push(current);
+        (previous, current) = (current, next);
+    }
+    if !neighbors(&start, &start_pipe)?.contains(&previous) {
+        return Err(PipeError::Disconnected(previous, start));
+    }
+    Ok(path)
 }
 
-fn neighbors(at: &Coord, pipe: &char) -> [Coord; 2] {
+fn neighbors(at: &Coord, pipe: &char) -> Result<[Coord; 2], PipeError> {
     match pipe {
-        '|' => [at.north(), at.south()],
-        '-' => [at.east(), at.west()],
-        'L' => [at.north(), at.east()],
-        'J' => [at.north(), at.west()],
-        '7' => [at.south(), at.west()],
-        'F' => [at.south(), at.east()],
-        _ => panic!("Unknown pipe type {pipe}"),
+        '|' => Ok([at.north(), at.south()]),
+        '-' => Ok([at.east(), at.west()]),
+        'L' => Ok([at.north(), at.east()]),
+        'J' => Ok([at.north(), at.west()]),
+        '7' => Ok([at.south(), at.west()]),
+        'F' => Ok([at.south(), at.east()]),
+        _ => Err(PipeError::NotAPipe(*at, *pipe)),
     }
 }
 
-fn read_input(input: &str) -> Result<(HashMap<Coord, char>, Coord), String> {
+fn read_input(input: &str) -> Result<(HashMap<Coord, char>, Coord), PipeError> {
     let mut maze = assign_coordinates(input).collect::<HashMap<_, _>>();
     let start = maze
         .iter()
         .find_map(|(k, &v)| if v == 'S' { Some(k) } else { None })
-        .ok_or_else(|| "Start not found".to_owned())
+        .ok_or(PipeError::NoStart)
         .copied()?;
     let start_pipe = determine_start_pipe(&maze, start)?;
     maze.entry(start).and_modify(|c| *c = start_pipe);
     Ok((maze, start))
 }
 
-fn determine_start_pipe(maze: &HashMap<Coord, char>, start: Coord) -> Result<char, String> {
-    let is_neighbor = |coord| {
-        maze.get(coord)
-            .filter(|&p| *p != '.')
-            .map(|p| neighbors(coord, p).contains(&start))
-            .unwrap_or(false)
-    };
-    match (
-        is_neighbor(&start.north()),
-        is_neighbor(&start.south()),
-        is_neighbor(&start.east()),
-        is_neighbor(&start.west()),
-    ) {
-        (true, true, _, _) => Some('|'),
-        (_, _, true, true) => Some('-'),
-        (true, _, true, _) => Some('L'),
-        (true, _, _, true) => Some('J'),
-        (_, true, _, true) => Some('7'),
-        (_, true, true, _) => Some('F'),
-        _ => None,
+/// Determines the pipe at the start as the only shape that makes a closed loop.
+fn determine_start_pipe(maze: &HashMap<Coord, char>, start: Coord) -> Result<char, PipeError> {
+    let closing = ['|', '-', 'L', 'J', '7', 'F']
+        .into_iter()
+        .filter(|pipe| walk_loop(start, *pipe, maze).is_ok())
+        .collect_vec();
+    match closing[..] {
+        [pipe] => Ok(pipe),
+        [] => Err(PipeError::NoStartPipe(start)),
+        _ => Err(PipeError::AmbiguousStart(start, closing)),
     }
-    .ok_or_else(|| format!("Could not determine start pipe"))
 }
 
 #[cfg(test)]
@@ -162,17 +271,109 @@ L---JF-JLJ.||-FJLJJ7
 L.L7LFJ|||||FJL7||LJ
 L7JLJL-JLJLJL--JLJ.L";
 
+    #[test]
+    fn loop_path_test() {
+        let (maze, start) = read_input(SAMPLE_INPUT_P2_1).unwrap();
+        let path = loop_path(start, &maze).unwrap();
+        assert_eq!(46, path.len());
+        assert_eq!(Coord::from(1, 1), path[0]);
+        assert!(path
+            .iter()
+            .chain(path.first())
+            .map_windows(|[a, b]| (a.x - b.x).abs() + (a.y - b.y).abs())
+            .all(|d| d == 1));
+        assert_eq!(Ok(23), part1(&distances(&path)));
+    }
+
+    #[test]
+    fn start_pipe_test() {
+        let (maze, start) = read_input(SAMPLE_INPUT_P2_2).unwrap();
+        assert_eq!('7', maze[&start]);
+        // Both the loop to the north-west and the one to the south-east go through the start
+        let two_loops = ".....\n.F7..\n.LS7.\n..LJ.\n.....";
+        assert_eq!(
+            Err(PipeError::AmbiguousStart(Coord::from(2, 2), vec!['J', 'F'])),
+            read_input(two_loops).map(|_| ())
+        );
+        assert_eq!(
+            Err(PipeError::NoStartPipe(Coord::from(1, 1))),
+            read_input("...\n.S-\n.|.").map(|_| ())
+        );
+        assert_eq!(Err(PipeError::NoStart), read_input("F7\nLJ").map(|_| ()));
+    }
+
+    #[test]
+    fn walk_loop_errors_test() {
+        let (maze, _) = read_input(SAMPLE_INPUT_P2_1).unwrap();
+        let start = Coord::from(1, 1);
+        assert_eq!(
+            Err(PipeError::NotAPipe(Coord::from(1, 0), '.')),
+            walk_loop(start, 'J', &maze)
+        );
+        assert_eq!(
+            Err(PipeError::OffTheMaze(Coord::from(0, -1))),
+            walk_loop(Coord::origin(), '|', &maze)
+        );
+        assert_eq!(
+            Err(PipeError::Disconnected(
+                Coord::from(1, 2),
+                Coord::from(2, 2)
+            )),
+            walk_loop(Coord::from(1, 2), '-', &maze)
+        );
+    }
+
+    #[test]
+    fn show_loop_test() {
+        let (maze, start) = read_input(SAMPLE_INPUT_P2_1).unwrap();
+        let path = loop_path(start, &maze).unwrap();
+        let shown = show_loop(&maze, &distances(&path), &enclosed_tiles(&path));
+        let plain = shown
+            .split("\x1b[")
+            .map(|part| part.split_once('m').map_or(part, |(_, token)| token))
+            .collect::<String>();
+        assert_eq!(
+            "\
+···········
+·┌───────┐·
+·│┌─────┐│·
+·││·····││·
+·││·····││·
+·│└─┐·┌─┘│·
+·│••│·│••│·
+·└──┘·└──┘·
+···········",
+            plain
+        );
+        assert!(shown.starts_with("\x1b[38;5;8m·\x1b[0m\x1b[38;5;8m·"));
+        assert!(shown.contains("\x1b[1;38;5;226m┌\x1b[0m"));
+        assert!(shown.contains("\x1b[38;5;46m•\x1b[0m"));
+    }
+
     #[test]
     fn part2_sample1_test() {
         let (maze, start) = read_input(SAMPLE_INPUT_P2_1).unwrap();
-        let distances = distances(start, &maze);
-        assert_eq!(4, part2(&maze, &distances));
+        let path = loop_path(start, &maze).unwrap();
+        assert_eq!(4, part2(&path));
+        assert_eq!(
+            HashSet::from([
+                Coord::from(2, 6),
+                Coord::from(3, 6),
+                Coord::from(7, 6),
+                Coord::from(8, 6)
+            ]),
+            enclosed_tiles(&path)
+        );
     }
 
     #[test]
     fn part2_sample2_test() {
         let (maze, start) = read_input(SAMPLE_INPUT_P2_2).unwrap();
-        let distances = distances(start, &maze);
-        assert_eq!(10, part2(&maze, &distances));
+        let path = loop_path(start, &maze).unwrap();
+        assert_eq!(10, part2(&path));
+        assert_eq!(10, enclosed_tiles(&path).len());
+        // The same loop walked the other way around
+        let reversed = path.iter().rev().copied().collect_vec();
+        assert_eq!(enclosed_tiles(&path), enclosed_tiles(&reversed));
     }
 }
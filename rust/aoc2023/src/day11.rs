@@ -1,42 +1,190 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
-use itertools::Itertools;
+use itertools::{Either, Itertools};
 
-use crate::util::{assign_coordinates, Coord};
+use crate::{
+    options::Options,
+    util::{assign_coordinates, Coord},
+};
 
-pub fn run(input: &str) -> Result<(), String> {
+/// The galaxies after expansion. They are numbered as in the puzzle: from 1, in reading order.
+struct Universe {
+    galaxies: Vec<Coord>,
+}
+impl Universe {
+    fn new(galaxies: &[Coord], expand_factor: i64) -> Universe {
+        Universe {
+            galaxies: expand(galaxies, expand_factor),
+        }
+    }
+
+    fn galaxy(&self, number: usize) -> Result<&Coord, String> {
+        number
+            .checked_sub(1)
+            .and_then(|i| self.galaxies.get(i))
+            .ok_or_else(|| format!("There is no galaxy {number}"))
+    }
+
+    fn distance(&self, a: usize, b: usize) -> Result<i64, String> {
+        Ok(self.galaxy(a)?.manhattan_distance(self.galaxy(b)?))
+    }
+
+    /// For every galaxy, the number of the closest other galaxy. Looks outward from the galaxy in
+    /// order of x, until the other galaxies are further away in x alone than the closest so far.
+    fn nearest(&self) -> Vec<Option<usize>> {
+        let by_x = (0..self.galaxies.len())
+            .sorted_by_key(|i| self.galaxies[*i].x)
+            .collect_vec();
+        let mut nearest = vec![None; self.galaxies.len()];
+        for (position, &i) in by_x.iter().enumerate() {
+            let galaxy = &self.galaxies[i];
+            let mut closest: Option<(i64, usize)> = None;
+            let right = by_x[position + 1..].iter();
+            let left = by_x[..position].iter().rev();
+            for side in [Either::Left(right), Either::Right(left)] {
+                for &j in side {
+                    let other = &self.galaxies[j];
+                    if closest.is_some_and(|(d, _)| d < (other.x - galaxy.x).abs()) {
+                        break;
+                    }
+                    let candidate = (galaxy.manhattan_distance(other), j);
+                    closest = Some(closest.map_or(candidate, |c| c.min(candidate)));
+                }
+            }
+            nearest[i] = closest.map(|(_, j)| j + 1);
+        }
+        nearest
+    }
+
+    /// For every galaxy, the number of the galaxy furthest away from it. In Manhattan distance,
+    /// that is the galaxy with the most extreme `x + y` or `x - y`.
+    fn farthest(&self) -> Vec<Option<usize>> {
+        let diagonals = [|c: &Coord| c.x + c.y, |c: &Coord| c.x - c.y];
+        let extremes = diagonals
+            .iter()
+            .flat_map(|diagonal| {
+                let by_diagonal = (0..self.galaxies.len())
+                    .sorted_by_key(|i| (diagonal(&self.galaxies[*i]), *i))
+                    .collect_vec();
+                [by_diagonal.first().copied(), by_diagonal.last().copied()]
+            })
+            .flatten()
+            .collect_vec();
+        self.galaxies
+            .iter()
+            .enumerate()
+            .map(|(i, galaxy)| {
+                extremes
+                    .iter()
+                    .filter(|j| **j != i)
+                    .max_by_key(|j| {
+                        (
+                            galaxy.manhattan_distance(&self.galaxies[**j]),
+                            -(**j as i64),
+                        )
+                    })
+                    .map(|j| j + 1)
+            })
+            .collect_vec()
+    }
+
+    /// Counts the pairs of galaxies by distance, in buckets of the given size, keyed by the
+    /// smallest distance in the bucket.
+    fn histogram(&self, bucket_size: i64) -> BTreeMap<i64, usize> {
+        self.galaxies
+            .iter()
+            .tuple_combinations()
+            .map(|(a, b)| a.manhattan_distance(b) / bucket_size * bucket_size)
+            .counts()
+            .into_iter()
+            .collect()
+    }
+}
+
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
     let galaxies = read_input(input);
     let part1 = distance_after_expand(&galaxies, 2);
     println!("Part 1: {part1}");
     let part2 = distance_after_expand(&galaxies, 1_000_000);
     println!("Part 2: {part2}");
+    if options.show {
+        println!("{}", show_universe(&Universe::new(&galaxies, 1_000_000))?);
+    }
     Ok(())
 }
 
-fn distance_after_expand(galaxies: &Vec<Coord>, expand_factor: i64) -> i64 {
+/// Lists the nearest and farthest galaxy of every galaxy, and how the distances are distributed.
+fn show_universe(universe: &Universe) -> Result<String, String> {
+    let mut lines = Vec::new();
+    let mut longest = 0;
+    for (i, (nearest, farthest)) in universe
+        .nearest()
+        .into_iter()
+        .zip(universe.farthest())
+        .enumerate()
+    {
+        let number = i + 1;
+        let describe = |other: Option<usize>| match other {
+            Some(other) => Ok(format!("{other} ({})", universe.distance(number, other)?)),
+            None => Ok::<_, String>("none".to_owned()),
+        };
+        if let Some(farthest) = farthest {
+            longest = longest.max(universe.distance(number, farthest)?);
+        }
+        lines.push(format!(
+            "Galaxy {number}: nearest {}, farthest {}",
+            describe(nearest)?,
+            describe(farthest)?
+        ));
+    }
+    let bucket_size = (longest / 10 + 1).max(1);
+    for (from, count) in universe.histogram(bucket_size) {
+        lines.push(format!("{from:>12}..{:<12} {count}", from + bucket_size));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn distance_after_expand(galaxies: &[Coord], expand_factor: i64) -> i64 {
     let expanded = expand(galaxies, expand_factor);
-    expanded
-        .iter()
-        .tuple_combinations::<(_, _)>()
-        .map(|(a, b)| a.manhattan_distance(b))
-        .sum()
+    axis_distances(expanded.iter().map(|c| c.x)) + axis_distances(expanded.iter().map(|c| c.y))
 }
 
-fn expand(galaxies: &Vec<Coord>, expand_factor: i64) -> Vec<Coord> {
-    let list_missing = |zs: HashSet<i64>| {
-        (0..*zs.iter().max().unwrap_or(&0))
-            .filter(|z| !zs.contains(z))
-            .collect_vec()
-    };
-    let count_smaller = |zs: &Vec<_>, limit| zs.iter().filter(|z| **z < limit).count() as i64;
-    let (xs, ys): (HashSet<_>, HashSet<_>) = galaxies.iter().map(|c| (c.x, c.y)).unzip();
-    let expand_x = list_missing(xs);
-    let expand_y = list_missing(ys);
+/// Sums the distances between all pairs of values. Once sorted, every value is at least as large
+/// as the ones before it, so its distance to all of them is `value * count - sum`.
+fn axis_distances(values: impl Iterator<Item = i64>) -> i64 {
+    values
+        .sorted_unstable()
+        .enumerate()
+        .fold((0, 0), |(total, sum), (count, value)| {
+            (total + value * count as i64 - sum, sum + value)
+        })
+        .0
+}
+
+fn expand(galaxies: &[Coord], expand_factor: i64) -> Vec<Coord> {
+    let empty_columns = empty_before(galaxies.iter().map(|c| c.x));
+    let empty_rows = empty_before(galaxies.iter().map(|c| c.y));
     galaxies
         .iter()
         .map(|coord| Coord {
-            x: coord.x + (expand_factor - 1) * count_smaller(&expand_x, coord.x),
-            y: coord.y + (expand_factor - 1) * count_smaller(&expand_y, coord.y),
+            x: coord.x + (expand_factor - 1) * empty_columns[coord.x as usize],
+            y: coord.y + (expand_factor - 1) * empty_rows[coord.y as usize],
+        })
+        .collect_vec()
+}
+
+/// Counts, for every row (or column) up to the last one with a galaxy, how many of the rows before
+/// it have no galaxies.
+fn empty_before(zs: impl Iterator<Item = i64>) -> Vec<i64> {
+    let occupied = zs.collect::<HashSet<_>>();
+    let last = occupied.iter().max().copied().unwrap_or(0);
+    (0..=last)
+        .scan(0, |empty, z| {
+            let before = *empty;
+            if !occupied.contains(&z) {
+                *empty += 1;
+            }
+            Some(before)
         })
         .collect_vec()
 }
@@ -64,6 +212,14 @@ mod tests {
 .......#..
 #...#.....";
 
+    /// Galaxies scattered with gaps in between, some of them in the same row or column.
+    fn scattered_galaxies(count: i64) -> Vec<Coord> {
+        (0..count)
+            .map(|i| Coord::from((i * 37) % 101 + i / 3, (i * i) % 89))
+            .unique()
+            .collect_vec()
+    }
+
     #[test]
     fn part1_test() {
         assert_eq!(374, distance_after_expand(&read_input(SAMPLE_INPUT), 2));
@@ -73,4 +229,70 @@ mod tests {
     fn part2_test() {
         assert_eq!(8410, distance_after_expand(&read_input(SAMPLE_INPUT), 100));
     }
+
+    #[test]
+    fn distance_test() {
+        let universe = Universe::new(&read_input(SAMPLE_INPUT), 2);
+        assert_eq!(Ok(9), universe.distance(5, 9));
+        assert_eq!(Ok(15), universe.distance(1, 7));
+        assert_eq!(Ok(17), universe.distance(3, 6));
+        assert_eq!(Ok(5), universe.distance(8, 9));
+        assert!(universe.distance(0, 1).is_err());
+        assert!(universe.distance(1, 10).is_err());
+    }
+
+    #[test]
+    fn nearest_and_farthest_test() {
+        let galaxies = scattered_galaxies(100);
+        for expand_factor in [1, 2, 10] {
+            let universe = Universe::new(&galaxies, expand_factor);
+            let nearest = universe.nearest();
+            let farthest = universe.farthest();
+            for a in 1..=universe.galaxies.len() {
+                let distance = |b| universe.distance(a, b).unwrap();
+                let others = (1..=universe.galaxies.len())
+                    .filter(|b| *b != a)
+                    .map(distance)
+                    .collect_vec();
+                assert_eq!(others.iter().min(), nearest[a - 1].map(distance).as_ref());
+                assert_eq!(others.iter().max(), farthest[a - 1].map(distance).as_ref());
+            }
+        }
+        let single = Universe::new(&[Coord::origin()], 2);
+        assert_eq!(vec![None], single.nearest());
+        assert_eq!(vec![None], single.farthest());
+    }
+
+    #[test]
+    fn histogram_test() {
+        let universe = Universe::new(&read_input(SAMPLE_INPUT), 2);
+        assert_eq!(
+            BTreeMap::from([(5, 18), (10, 11), (15, 7)]),
+            universe.histogram(5)
+        );
+        let total = universe.histogram(1).into_iter().map(|(d, n)| d * n as i64);
+        assert_eq!(374, total.sum::<i64>());
+    }
+
+    #[test]
+    fn empty_before_test() {
+        let galaxies = read_input(SAMPLE_INPUT);
+        assert_eq!(
+            vec![0, 0, 0, 1, 1, 1, 2, 2, 2, 3],
+            empty_before(galaxies.iter().map(|c| c.x))
+        );
+    }
+
+    #[test]
+    fn axis_distances_test() {
+        // compare with summing over all pairs
+        let galaxies = scattered_galaxies(200);
+        let expanded = expand(&galaxies, 10);
+        let pairwise: i64 = expanded
+            .iter()
+            .tuple_combinations()
+            .map(|(a, b)| a.manhattan_distance(b))
+            .sum();
+        assert_eq!(pairwise, distance_after_expand(&galaxies, 10));
+    }
 }
@@ -1,9 +1,9 @@
 use itertools::Itertools;
 
-use crate::util::read_all_sep_by;
+use crate::{options::Options, util::read_all_sep_by};
 
 #[allow(unused_variables)]
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let springs = read_input(input)?;
     let part1 = count_possibilities(&springs);
     println!("Part 1: {}", part1);
@@ -1,47 +1,94 @@
-use std::collections::HashMap;
+use std::fmt::Display;
 
 use itertools::Itertools;
 
-use crate::util::{assign_coordinates, Coord};
+use crate::{options::Options, util::Coord};
+
+/// A pattern of ash and rocks as bitmasks: bit `x` of `rows[y]`, and bit `y` of `cols[x]`, is set
+/// when there is a rock at `(x, y)`.
+#[derive(Debug)]
+struct Pattern {
+    rows: Vec<u64>,
+    cols: Vec<u64>,
+}
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines = self.rows.iter().map(|row| {
+            (0..self.cols.len())
+                .map(|x| if row >> x & 1 == 1 { '#' } else { '.' })
+                .collect::<String>()
+        });
+        write!(f, "{}", lines.format("\n"))
+    }
+}
 
-type Pattern = HashMap<Coord, char>;
 #[derive(PartialEq, Eq, Debug)]
 enum Reflection {
     Row(i64),
     Col(i64),
 }
 
-pub fn run(input: &str) -> Result<(), String> {
-    let patterns = read_patterns(input);
-    let part1 = part1(&patterns)?;
+/// A line of reflection, with the pairs of mirrored cells that differ. Either cell of a pair could
+/// be the smudge.
+#[derive(PartialEq, Eq, Debug)]
+struct Mirror {
+    reflection: Reflection,
+    /// The number of rows or columns on either side of the line that are mirrored, the rest falls
+    /// outside of the pattern on the other side.
+    extent: i64,
+    smudges: Vec<(Coord, Coord)>,
+}
+impl Display for Mirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (line, lines) = match self.reflection {
+            Reflection::Row(_) => ("row", "rows"),
+            Reflection::Col(_) => ("column", "columns"),
+        };
+        let (Reflection::Row(position) | Reflection::Col(position)) = self.reflection;
+        write!(
+            f,
+            "Reflection after {line} {position}, mirroring {} {lines} with {} smudge(s)",
+            self.extent,
+            self.smudges.len()
+        )
+    }
+}
+
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
+    let patterns = read_patterns(input)?;
+    if options.show {
+        for (i, pattern) in patterns.iter().enumerate() {
+            for smudges in [0, 1] {
+                let mirrors = find_reflections(pattern, smudges);
+                println!(
+                    "Pattern {} has {} reflection(s) with {smudges} smudge(s)",
+                    i + 1,
+                    mirrors.len()
+                );
+                for mirror in mirrors {
+                    println!("{mirror}\n{}\n", show_mirror(pattern, &mirror));
+                }
+            }
+        }
+    }
+    let part1 = summarize(&patterns, 0)?;
     println!("Part 1: {part1}");
-    let part2 = part2(&patterns)?;
+    let part2 = summarize(&patterns, 1)?;
     println!("Part 2: {part2}");
     Ok(())
 }
 
-fn part1(patterns: &Vec<Pattern>) -> Result<i64, String> {
+/// Sums the reflection summaries of the patterns, where every pattern has exactly `smudges`
+/// smudges.
+fn summarize(patterns: &[Pattern], smudges: u32) -> Result<i64, String> {
     patterns
         .iter()
         .map(|p| {
-            find_reflection(p, None, &None)
-                .ok_or_else(|| format!("Could not determine reflection for {:?}", p))
+            find_reflection(p, smudges)
+                .map(|mirror| reflection_summary(&mirror.reflection))
+                .ok_or_else(|| format!("Could not determine reflection for\n{p}"))
         })
-        .collect::<Result<Vec<_>, _>>()
-        .map(|reflection| reflection.iter().map(reflection_summary).sum())
-}
-
-fn part2(patterns: &Vec<Pattern>) -> Result<i64, String> {
-    patterns
-        .iter()
-        .map(|p| {
-            let unsmudged_reflection = find_reflection(p, None, &None);
-            p.keys()
-                .find_map(|c| find_reflection(p, Some(*c), &unsmudged_reflection))
-                .ok_or_else(|| format!("Could not determine reflection for {:?}", p))
-        })
-        .collect::<Result<Vec<_>, _>>()
-        .map(|reflection| reflection.iter().map(reflection_summary).sum())
+        .sum()
 }
 
 fn reflection_summary(reflection: &Reflection) -> i64 {
@@ -51,86 +98,124 @@ fn reflection_summary(reflection: &Reflection) -> i64 {
     }
 }
 
-fn invert(terrain: &char) -> char {
-    match *terrain {
-        '#' => '.',
-        _ => '#',
-    }
+/// Finds a line of reflection, preferring columns over rows, where exactly `smudges` cells don't
+/// match their mirror image.
+fn find_reflection(pattern: &Pattern, smudges: u32) -> Option<Mirror> {
+    find_reflections(pattern, smudges).into_iter().next()
 }
 
-fn find_reflection(
-    pattern: &Pattern,
-    smudge: Option<Coord>,
-    skip: &Option<Reflection>,
-) -> Option<Reflection> {
-    let get_terrain = |coord| {
-        let terrain = pattern.get(&coord).unwrap();
-        let result = smudge
-            .filter(|c| *c == coord)
-            .map(|_| invert(terrain))
-            .unwrap_or(*terrain);
-        result
-    };
-    let (max_x, max_y) = pattern
-        .keys()
-        .fold((-1_i64, -1_i64), |(max_x, max_y), Coord { x, y }| {
-            (max_x.max(*x), max_y.max(*y))
-        });
-    let col_result = find_column_reflection(max_x, max_y, &get_terrain)
-        .filter(|refl| skip.as_ref().map(|r| !r.eq(refl)).unwrap_or(true))
-        .next();
-
-    let result = col_result.or_else(|| {
-        find_row_reflection(max_x, max_y, &get_terrain)
-            .filter(|refl| skip.as_ref().map(|r| !r.eq(refl)).unwrap_or(true))
-            .next()
+/// Finds all lines of reflection, columns first, where exactly `smudges` cells don't match their
+/// mirror image.
+fn find_reflections(pattern: &Pattern, smudges: u32) -> Vec<Mirror> {
+    let extent = |position: i64, len: usize| position.min(len as i64 - position);
+    let columns = mirror_positions(&pattern.cols, smudges).map(|(col, mismatches)| Mirror {
+        reflection: Reflection::Col(col),
+        extent: extent(col, pattern.cols.len()),
+        smudges: mismatches
+            .into_iter()
+            .map(|(left, right, y)| (Coord::from(left, y), Coord::from(right, y)))
+            .collect(),
     });
-    result
+    let rows = mirror_positions(&pattern.rows, smudges).map(|(row, mismatches)| Mirror {
+        reflection: Reflection::Row(row),
+        extent: extent(row, pattern.rows.len()),
+        smudges: mismatches
+            .into_iter()
+            .map(|(above, below, x)| (Coord::from(x, above), Coord::from(x, below)))
+            .collect(),
+    });
+    columns.chain(rows).collect()
 }
 
-fn find_column_reflection<'a>(
-    max_x: i64,
-    max_y: i64,
-    get_terrain: &'a impl Fn(Coord) -> char,
-) -> impl Iterator<Item = Reflection> + 'a {
-    (1..=max_x)
-        .filter(move |&col| {
-            let num_cols = col.min((max_x + 1) - col);
-            (col..col + num_cols).all(|x| {
-                (0..=max_y).all(|y| {
-                    let x_left = col - 1 - (x - col);
-                    let x_right = x;
-                    get_terrain(Coord::from(x_right, y)).eq(&get_terrain(Coord::from(x_left, y)))
-                })
+/// Draws the pattern with the mirror marked like in the puzzle: `><` above and below the columns
+/// on either side of a vertical mirror, `v` and `^` next to the rows on either side of a
+/// horizontal one.
+fn show_mirror(pattern: &Pattern, mirror: &Mirror) -> String {
+    let lines = pattern.to_string();
+    match mirror.reflection {
+        Reflection::Col(col) => {
+            let marker = format!("{}><", " ".repeat(col as usize - 1));
+            format!("{marker}\n{lines}\n{marker}")
+        }
+        Reflection::Row(row) => lines
+            .lines()
+            .zip(0..)
+            .map(|(line, y)| {
+                let marker = match y - row {
+                    -1 => 'v',
+                    0 => '^',
+                    _ => ' ',
+                };
+                format!("{marker}{line}{marker}")
             })
-        })
-        .map(Reflection::Col)
+            .join("\n"),
+    }
 }
 
-fn find_row_reflection<'a>(
-    max_x: i64,
-    max_y: i64,
-    get_terrain: &'a impl Fn(Coord) -> char,
-) -> impl Iterator<Item = Reflection> + 'a {
-    (1..=max_y)
-        .filter(move |row| {
-            let num_rows = *row.min(&((max_y + 1) - *row));
-            (*row..row + num_rows).all(|y| {
-                (0..=max_x).all(|x| {
-                    let y_left = row - 1 - (y - row);
-                    let y_right = y;
-                    get_terrain(Coord::from(x, y_right)).eq(&get_terrain(Coord::from(x, y_left)))
-                })
+/// Finds the positions between lines where the lines on either side mirror each other, except for
+/// exactly `smudges` cells. Those cells are returned as the indices of the two mirrored lines and
+/// the index of the cell within them.
+fn mirror_positions(
+    lines: &[u64],
+    smudges: u32,
+) -> impl Iterator<Item = (i64, Vec<(i64, i64, i64)>)> + '_ {
+    (1..lines.len()).filter_map(move |position| {
+        let pairs = (0..position).rev().zip(position..lines.len());
+        let differences = pairs
+            .map(|(before, after)| (before, after, lines[before] ^ lines[after]))
+            .filter(|(_, _, difference)| *difference != 0)
+            .collect_vec();
+        if differences
+            .iter()
+            .map(|(_, _, d)| d.count_ones())
+            .sum::<u32>()
+            != smudges
+        {
+            return None;
+        }
+        let mismatches = differences
+            .into_iter()
+            .flat_map(|(before, after, difference)| {
+                (0..u64::BITS as i64)
+                    .filter(move |bit| difference >> bit & 1 == 1)
+                    .map(move |bit| (before as i64, after as i64, bit))
             })
-        })
-        .map(Reflection::Row)
+            .collect_vec();
+        Some((position as i64, mismatches))
+    })
 }
 
-fn read_patterns(input: &str) -> Vec<Pattern> {
-    input
-        .split("\n\n")
-        .map(|pattern| assign_coordinates(pattern).collect::<Pattern>())
-        .collect_vec()
+fn read_patterns(input: &str) -> Result<Vec<Pattern>, String> {
+    input.split("\n\n").map(read_pattern).collect()
+}
+
+fn read_pattern(input: &str) -> Result<Pattern, String> {
+    let lines = input.lines().collect_vec();
+    let width = lines.first().map_or(0, |l| l.len());
+    if lines.iter().any(|l| l.len() != width) {
+        return Err(format!("Pattern is not rectangular:\n{input}"));
+    }
+    if 64 < width || 64 < lines.len() {
+        return Err(format!("Pattern is larger than 64 by 64:\n{input}"));
+    }
+    let rows = lines
+        .iter()
+        .map(|l| {
+            l.chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '#')
+                .fold(0, |row, (x, _)| row | 1 << x)
+        })
+        .collect_vec();
+    let cols = (0..width)
+        .map(|x| {
+            rows.iter()
+                .enumerate()
+                .filter(|(_, row)| *row >> x & 1 == 1)
+                .fold(0, |col, (y, _)| col | 1 << y)
+        })
+        .collect_vec();
+    Ok(Pattern { rows, cols })
 }
 
 #[cfg(test)]
@@ -154,13 +239,111 @@ mod tests {
 ..##..###
 #....#..#";
 
+    #[test]
+    fn read_pattern_test() {
+        let patterns = read_patterns(SAMPLE_INPUT).unwrap();
+        assert_eq!(0b011001101, patterns[0].rows[0]);
+        assert_eq!(0b1001101, patterns[0].cols[0]);
+        assert_eq!(SAMPLE_INPUT, patterns.iter().join("\n\n"));
+        assert!(read_pattern("#.\n#").is_err());
+    }
+
     #[test]
     fn part1_test() {
-        assert_eq!(405, part1(&read_patterns(SAMPLE_INPUT)).unwrap());
+        assert_eq!(
+            405,
+            summarize(&read_patterns(SAMPLE_INPUT).unwrap(), 0).unwrap()
+        );
     }
 
     #[test]
     fn part2_test() {
-        assert_eq!(400, part2(&read_patterns(SAMPLE_INPUT)).unwrap());
+        assert_eq!(
+            400,
+            summarize(&read_patterns(SAMPLE_INPUT).unwrap(), 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn find_reflections_test() {
+        // Symmetrical both ways, and in the middle of both halves of the columns
+        let pattern = read_pattern("#..##..#\n.##..##.\n.##..##.\n#..##..#").unwrap();
+        let mirrors = find_reflections(&pattern, 0);
+        assert_eq!(
+            vec![
+                (Reflection::Col(2), 2),
+                (Reflection::Col(4), 4),
+                (Reflection::Col(6), 2),
+                (Reflection::Row(2), 2)
+            ],
+            mirrors
+                .into_iter()
+                .map(|m| (m.reflection, m.extent))
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn show_mirror_test() {
+        let patterns = read_patterns(SAMPLE_INPUT).unwrap();
+        let mirror = find_reflection(&patterns[0], 0).unwrap();
+        assert_eq!(
+            "Reflection after column 5, mirroring 4 columns with 0 smudge(s)",
+            mirror.to_string()
+        );
+        assert_eq!(
+            "    ><\n#.##..##.",
+            show_mirror(&patterns[0], &mirror)
+                .lines()
+                .take(2)
+                .join("\n")
+        );
+        let mirror = find_reflection(&patterns[1], 0).unwrap();
+        assert_eq!(
+            [
+                " #...##..# ",
+                " #....#..# ",
+                " ..##..### ",
+                "v#####.##.v",
+                "^#####.##.^",
+                " ..##..### ",
+                " #....#..# "
+            ]
+            .join("\n"),
+            show_mirror(&patterns[1], &mirror)
+        );
+    }
+
+    #[test]
+    fn smudge_test() {
+        let patterns = read_patterns(SAMPLE_INPUT).unwrap();
+        assert_eq!(
+            Some(Mirror {
+                reflection: Reflection::Row(3),
+                extent: 3,
+                smudges: vec![(Coord::from(0, 0), Coord::from(0, 5))]
+            }),
+            find_reflection(&patterns[0], 1)
+        );
+        assert_eq!(
+            Some(Mirror {
+                reflection: Reflection::Row(1),
+                extent: 1,
+                smudges: vec![(Coord::from(4, 0), Coord::from(4, 1))]
+            }),
+            find_reflection(&patterns[1], 1)
+        );
+        // A mirror after the first column of the first pattern takes two smudges
+        assert_eq!(
+            Some(Mirror {
+                reflection: Reflection::Col(1),
+                extent: 1,
+                smudges: vec![
+                    (Coord::from(0, 0), Coord::from(1, 0)),
+                    (Coord::from(0, 6), Coord::from(1, 6))
+                ]
+            }),
+            find_reflection(&patterns[0], 2)
+        );
     }
 }
@@ -1,155 +1,234 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 use itertools::Itertools;
 
-use crate::util::{assign_coordinates, Coord};
+use crate::{
+    options::Options,
+    util::{cycle::find_cycle, read, Coord, Direction},
+    viz::{Frames, Image},
+};
 
-#[derive(Debug, Clone)]
+/// The platform as a grid of bytes, row by row: `O` for rounded rocks, `#` for cube-shaped rocks
+/// and `.` for empty space.
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Platform {
-    size: (i64, i64),
-    rocks: HashMap<Coord, char>,
+    width: usize,
+    height: usize,
+    tiles: Vec<u8>,
+}
+impl Platform {
+    fn tile(&self, x: usize, y: usize) -> u8 {
+        self.tiles[y * self.width + x]
+    }
+
+    /// Rolls all rounded rocks as far as they go in the direction, in place. Every row or column is
+    /// scanned from the side the rocks roll towards, keeping track of where the next rock ends up.
+    fn tilt(&mut self, direction: Direction) {
+        let (width, height) = (self.width, self.height);
+        let (lanes, length) = match direction {
+            Direction::North | Direction::South => (width, height),
+            Direction::West | Direction::East => (height, width),
+        };
+        for lane in 0..lanes {
+            // the index of the tile `i` tiles away from the side the rocks roll towards
+            let index = |i: usize| match direction {
+                Direction::North => i * width + lane,
+                Direction::South => (height - 1 - i) * width + lane,
+                Direction::West => lane * width + i,
+                Direction::East => lane * width + width - 1 - i,
+            };
+            let mut free = 0;
+            for i in 0..length {
+                match self.tiles[index(i)] {
+                    b'#' => free = i + 1,
+                    b'O' => {
+                        self.tiles.swap(index(i), index(free));
+                        free += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// The positions of the rounded rocks as a bitset, which is all that changes when tilting.
+    fn key(&self) -> Vec<u64> {
+        let mut bits = vec![0; self.tiles.len().div_ceil(64)];
+        for (i, _) in self.tiles.iter().enumerate().filter(|(_, t)| **t == b'O') {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+        bits
+    }
 }
 
-pub fn run(input: &str) -> Result<(), String> {
-    let platform = read_input(input);
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
+    let platform = read_input(input)?;
     let part1 = part1(&platform);
     println!("Part 1: {part1}");
+    if options.show {
+        let mut tilted = platform.clone();
+        tilted.tilt(Direction::North);
+        println!("{}", to_map(&tilted));
+    }
     let part2 = part2(&platform);
     println!("Part 2: {part2}");
+    if let Some(program) = &options.tilt {
+        println!("{}", show_program(&platform, program)?);
+    }
+    if let Some(format) = &options.frames {
+        let mut frames = Frames::new("day14_spin_cycles", format)?;
+        animate_spin_cycles(&platform, &mut frames);
+        println!("Wrote {}", frames.finish()?);
+    }
 
     Ok(())
 }
 
 fn part1(platform: &Platform) -> i64 {
-    let tilted = tilt(&platform, (0, -1));
-    load(&tilted)
+    let mut tilted = platform.clone();
+    tilted.tilt(Direction::North);
+    load(&tilted, Direction::North)
 }
 
-fn load(platform: &Platform) -> i64 {
-    platform
-        .rocks
-        .iter()
-        .filter_map(|(Coord { y, .. }, rock)| {
-            Some(rock)
-                .filter(|r| **r == 'O')
-                .map(|_| platform.size.1 - y)
-        })
+/// The total load of the rounded rocks on the beams on the given side: every rock adds its
+/// distance to the opposite side, counting its own row or column.
+fn load(platform: &Platform, side: Direction) -> i64 {
+    let (width, height) = (platform.width, platform.height);
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|(x, y)| platform.tile(*x, *y) == b'O')
+        .map(|(x, y)| match side {
+            Direction::North => height - y,
+            Direction::South => y + 1,
+            Direction::West => width - x,
+            Direction::East => x + 1,
+        } as i64)
         .sum()
 }
 
 fn part2(platform: &Platform) -> i64 {
-    const CYCLE_COUNT: i64 = 1_000_000_000;
-    let mut seen = HashMap::<String, (i64, usize)>::new();
-    let cycles = (0..CYCLE_COUNT)
-        .scan(platform.clone(), |p, _| {
-            let tilted = spin_cycle(&p);
-            let result = (load(&tilted), to_map(&tilted));
-            *p = tilted;
-            Some(result)
-        })
-        .enumerate()
-        .take_while_inclusive(|(i, (load, map))| {
-            if seen.contains_key(map) {
-                false
-            } else {
-                seen.insert(map.to_owned(), (*load, *i));
-                true
+    const CYCLE_COUNT: u64 = 1_000_000_000;
+    load(
+        &run_program(platform, &[(SPIN_CYCLE.to_vec(), CYCLE_COUNT)]),
+        Direction::North,
+    )
+}
+
+/// Sequences of tilts, each repeated a number of times.
+type TiltProgram = Vec<(Vec<Direction>, u64)>;
+
+/// Reads a tilt program: sequences of tilts separated by commas, each a string of directions
+/// (`N`, `W`, `S` or `E`) optionally followed by `*` and the number of times to repeat it. For
+/// example, `NWSE*1000000000` does the spin cycles of part 2.
+fn read_program(program: &str) -> Result<TiltProgram, String> {
+    program
+        .split(',')
+        .map(|part| {
+            let (tilts, repeat) = match part.trim().split_once('*') {
+                Some((tilts, repeat)) => (tilts, read::<u64>(repeat.trim())?),
+                None => (part.trim(), 1),
+            };
+            let directions = tilts
+                .chars()
+                .map(|c| match c {
+                    'N' => Ok(Direction::North),
+                    'W' => Ok(Direction::West),
+                    'S' => Ok(Direction::South),
+                    'E' => Ok(Direction::East),
+                    _ => Err(format!("Unknown tilt {c} in {program}")),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            if directions.is_empty() {
+                return Err(format!("Missing tilts in {program}"));
             }
+            Ok((directions, repeat))
         })
-        .map(|(_, load)| load)
-        .collect_vec();
-    let ((_, from), to) = (
-        *seen.get(&cycles.last().unwrap().1).unwrap(),
-        cycles.len() - 1,
-    );
-    let cycle_length = to - from;
-    let load_index = (CYCLE_COUNT - from as i64) % cycle_length as i64;
-    cycles.get(from - 1 + load_index as usize).unwrap().0
+        .collect()
 }
 
-fn spin_cycle(platform: &Platform) -> Platform {
-    [(0, -1), (-1, 0), (0, 1), (1, 0)]
+/// Runs the tilt program. Repeated tilts are skipped ahead by finding where they start to cycle.
+fn run_program(platform: &Platform, program: &[(Vec<Direction>, u64)]) -> Platform {
+    program
         .iter()
-        .fold(platform.clone(), |p: Platform, dir| tilt(&p, *dir))
+        .fold(platform.clone(), |platform, (directions, repeat)| {
+            let tilt_all = |p: &Platform| {
+                let mut p = p.clone();
+                directions.iter().for_each(|d| p.tilt(*d));
+                p
+            };
+            match repeat {
+                0 => platform,
+                1 => tilt_all(&platform),
+                _ => find_cycle(platform, tilt_all, Platform::key)
+                    .after(*repeat)
+                    .clone(),
+            }
+        })
 }
 
-fn tilt(Platform { size, rocks }: &Platform, direction: (i64, i64)) -> Platform {
-    let mut new_rocks = rocks.clone();
-    move_order(direction, *size)
+/// Shows the loads on all sides and the platform after running the tilt program.
+fn show_program(platform: &Platform, program: &str) -> Result<String, String> {
+    let tilted = run_program(platform, &read_program(program)?);
+    let loads = SPIN_CYCLE
         .iter()
-        .for_each(|coord| move_rock(&mut new_rocks, &coord, direction, *size));
-
-    Platform {
-        size: *size,
-        rocks: new_rocks,
-    }
+        .map(|side| format!("Load on the {side:?} side: {}", load(&tilted, *side)))
+        .join("\n");
+    Ok(format!("{loads}\n{}", to_map(&tilted)))
 }
 
-fn move_order(direction: (i64, i64), (size_x, size_y): (i64, i64)) -> Vec<Coord> {
-    match direction {
-        (0, dy) if dy < 0 => (0..size_y)
-            .flat_map(move |y| (0..size_x).map(move |x| Coord::from(x, y)))
-            .collect_vec(),
-        (0, _dy) => (0..size_y)
-            .rev()
-            .flat_map(move |y| (0..size_x).map(move |x| Coord::from(x, y)))
-            .collect_vec(),
-        (dx, 0) if dx < 0 => (0..size_x)
-            .flat_map(move |x| (0..size_y).map(move |y| Coord::from(x, y)))
-            .collect_vec(),
-        (_dx, 0) => (0..size_x)
-            .rev()
-            .flat_map(move |x| (0..size_y).map(move |y| Coord::from(x, y)))
-            .collect_vec(),
-        _ => panic!("Not a direction: {:?}", direction),
+/// Tilt directions of a spin cycle, which are also the sides to report loads for.
+const SPIN_CYCLE: [Direction; 4] = [
+    Direction::North,
+    Direction::West,
+    Direction::South,
+    Direction::East,
+];
+
+/// Captures the platform after every tilt, until a spin cycle ends in a state seen before.
+fn animate_spin_cycles(platform: &Platform, frames: &mut Frames) {
+    let mut seen = HashSet::new();
+    let mut current = platform.clone();
+    frames.capture(&render_platform(&current));
+    while seen.insert(current.key()) {
+        for direction in SPIN_CYCLE {
+            current.tilt(direction);
+            frames.capture(&render_platform(&current));
+        }
     }
 }
 
-fn move_rock(
-    rocks: &mut HashMap<Coord, char>,
-    coord: &Coord,
-    (dir_x, dir_y): (i64, i64),
-    (size_x, size_y): (i64, i64),
-) {
-    let within_bounds =
-        |coord: &Coord| 0 <= coord.x && coord.x < size_x && 0 <= coord.y && coord.y < size_y;
-    match rocks.get(coord) {
-        Some('O') => {
-            rocks.remove(coord);
-            let new_position = (0..)
-                .map(|n| coord.move_by((dir_x * n, dir_y * n)))
-                .take_while(|coord| within_bounds(&coord) && !rocks.contains_key(coord))
-                .last()
-                .unwrap_or(*coord);
-            rocks.insert(new_position, 'O');
+fn render_platform(platform: &Platform) -> Image {
+    let bottom_right = Coord::from(platform.width as i64 - 1, platform.height as i64 - 1);
+    Image::render(Coord::origin(), bottom_right, 4, |c| {
+        match platform.tile(c.x as usize, c.y as usize) {
+            b'O' => [0xd0, 0xd0, 0xd0],
+            b'#' => [0x80, 0x30, 0x30],
+            _ => [0x1c, 0x1c, 0x1c],
         }
-        _ => {}
-    }
+    })
 }
 
-fn read_input(input: &str) -> Platform {
-    let (rocks, (max_x, max_y)) = assign_coordinates(input).fold(
-        (HashMap::new(), (-1, -1)),
-        |(mut rocks, (max_x, max_y)), (coord, rock)| {
-            if rock != '.' {
-                rocks.insert(coord, rock);
-            }
-            (rocks, (max_x.max(coord.x), max_y.max(coord.y)))
-        },
-    );
-    Platform {
-        size: (max_x + 1, max_y + 1),
-        rocks,
+fn read_input(input: &str) -> Result<Platform, String> {
+    let lines = input.lines().collect_vec();
+    let width = lines.first().map_or(0, |l| l.len());
+    if let Some(line) = lines.iter().find(|l| l.len() != width) {
+        return Err(format!("Line {line} is not {width} tiles wide"));
+    }
+    if let Some(tile) = input.chars().find(|c| !"O#.\n".contains(*c)) {
+        return Err(format!("Unknown tile {tile}"));
     }
+    Ok(Platform {
+        width,
+        height: lines.len(),
+        tiles: lines.iter().flat_map(|l| l.bytes()).collect(),
+    })
 }
 
 fn to_map(platform: &Platform) -> String {
-    (0..platform.size.1)
-        .map(|line| {
-            (0..platform.size.0)
-                .map(|col| platform.rocks.get(&Coord::from(col, line)).unwrap_or(&'.'))
-                .join("")
-        })
+    platform
+        .tiles
+        .chunks(platform.width.max(1))
+        .map(|row| String::from_utf8_lossy(row))
         .join("\n")
 }
 
@@ -171,21 +250,106 @@ O.#..O.#.#
 
     #[test]
     fn part1_test() {
-        assert_eq!(136, part1(&read_input(SAMPLE_INPUT)))
+        assert_eq!(136, part1(&read_input(SAMPLE_INPUT).unwrap()))
+    }
+
+    #[test]
+    fn tilt_test() {
+        let mut platform = read_input("O.#O\n..O.\n.O.O").unwrap();
+        platform.tilt(Direction::East);
+        assert_eq!(".O#O\n...O\n..OO", to_map(&platform));
+        platform.tilt(Direction::South);
+        assert_eq!("..#O\n...O\n.OOO", to_map(&platform));
+        platform.tilt(Direction::West);
+        assert_eq!("..#O\nO...\nOOO.", to_map(&platform));
+        platform.tilt(Direction::North);
+        assert_eq!("OO#O\nO.O.\n....", to_map(&platform));
+        assert!(read_input("O.\n.").is_err());
+        assert!(read_input("O?").is_err());
     }
 
     #[test]
     fn spin_cycle_test() {
-        (1..4).fold(read_input(SAMPLE_INPUT), |p, _| {
-            let r = spin_cycle(&p);
-            println!("{}", to_map(&r));
-            println!();
-            r
-        });
+        let platform = read_input(SAMPLE_INPUT).unwrap();
+        let platform = run_program(&platform, &read_program("NWSE").unwrap());
+        assert_eq!(
+            "\
+.....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....",
+            to_map(&platform)
+        );
+    }
+
+    #[test]
+    fn key_test() {
+        let platform = read_input(SAMPLE_INPUT).unwrap();
+        let mut tilted = platform.clone();
+        tilted.tilt(Direction::North);
+        assert_eq!(2, platform.key().len());
+        assert_ne!(platform.key(), tilted.key());
+        tilted.tilt(Direction::North);
+        let mut twice = platform.clone();
+        twice.tilt(Direction::North);
+        assert_eq!(twice.key(), tilted.key());
+    }
+
+    #[test]
+    fn load_test() {
+        let platform = read_input(".O.\n..#\nO..").unwrap();
+        assert_eq!(
+            vec![4, 5, 4, 3],
+            SPIN_CYCLE
+                .iter()
+                .map(|side| load(&platform, *side))
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn read_program_test() {
+        assert_eq!(
+            Ok(vec![(SPIN_CYCLE.to_vec(), 1_000_000_000)]),
+            read_program("NWSE*1000000000")
+        );
+        assert_eq!(
+            Ok(vec![
+                (vec![Direction::North], 1),
+                (vec![Direction::East], 1),
+                (vec![Direction::North], 1)
+            ]),
+            read_program("N,E,N")
+        );
+        assert!(read_program("NX").is_err());
+        assert!(read_program("N,,E").is_err());
+        assert!(read_program("N*many").is_err());
+    }
+
+    #[test]
+    fn run_program_test() {
+        let platform = read_input(SAMPLE_INPUT).unwrap();
+        let mut expected = platform.clone();
+        [Direction::North, Direction::East, Direction::North]
+            .iter()
+            .for_each(|d| expected.tilt(*d));
+        let tilted = run_program(&platform, &read_program("N,E,N").unwrap());
+        assert_eq!(to_map(&expected), to_map(&tilted));
+        let spun = run_program(&platform, &read_program("NWSE*3").unwrap());
+        let expected = run_program(&platform, &read_program("N,W,S,E,N,W,S,E,N,W,S,E").unwrap());
+        assert_eq!(to_map(&expected), to_map(&spun));
+        let shown = show_program(&platform, "NWSE*1000000000").unwrap();
+        assert!(shown.starts_with("Load on the North side: 64\n"));
     }
 
     #[test]
     fn part2_test() {
-        assert_eq!(64, part2(&read_input(SAMPLE_INPUT)));
+        assert_eq!(64, part2(&read_input(SAMPLE_INPUT).unwrap()));
     }
 }
@@ -2,6 +2,8 @@ use std::array;
 
 use itertools::Itertools;
 
+use crate::options::Options;
+
 enum Instruction<'a> {
     Place(&'a str, u8),
     Remove(&'a str),
@@ -15,7 +17,7 @@ impl Instruction<'_> {
     }
 }
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let instructions = read_input(input);
     let part1: u64 = instructions.iter().map(|s| run_hash(s)).sum();
     println!("Part 1: {part1}");
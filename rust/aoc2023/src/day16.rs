@@ -5,7 +5,11 @@ use std::{
 
 use itertools::Itertools;
 
-use crate::util::{assign_coordinates, Coord, Direction};
+use crate::{
+    options::Options,
+    util::{assign_coordinates, Coord, Direction},
+    viz::{self, Frames, Image},
+};
 
 #[derive(Clone, Copy)]
 struct Beam(Coord, Direction);
@@ -21,64 +25,89 @@ impl Direction {
     }
 }
 
-const SHOW_MAP: bool = false;
-
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
     let mirror_cave = read_cave(input);
     let part1 = part1(&mirror_cave);
     println!("Part 1: {part1}");
-    let part2 = power_up(&mirror_cave);
+    if options.show {
+        let energized = energize(&mirror_cave, Beam(Coord::origin(), Direction::East));
+        println!("{}", show_beams(&mirror_cave, &energized));
+    }
+    let (winner, part2) = strongest_beam(&mirror_cave);
     println!("Part 2: {part2}");
+    if options.show {
+        println!(
+            "{}",
+            show_beams(&mirror_cave, &energize(&mirror_cave, winner))
+        );
+    }
+    if let Some(format) = &options.export {
+        let image = render_beams(&mirror_cave, &energize(&mirror_cave, winner));
+        let path = viz::save(&image, "day16_beams_p2", format)?;
+        println!("Exported beams to {path}");
+    }
+    if let Some(format) = &options.frames {
+        let mut frames = Frames::new("day16_beams_p2", format)?;
+        energize_stepwise(&mirror_cave, winner, |beams| {
+            frames.capture(&render_beams(&mirror_cave, beams))
+        });
+        println!("Wrote {}", frames.finish()?);
+    }
     Ok(())
 }
 
 fn part1(mirror_cave: &HashMap<Coord, char>) -> usize {
-    let energized = energize(&mirror_cave, Beam(Coord::origin(), Direction::East));
-    if SHOW_MAP {
-        println!("{}", show_beams(mirror_cave, &energized))
-    }
-    energized.keys().count()
+    energize(mirror_cave, Beam(Coord::origin(), Direction::East))
+        .keys()
+        .count()
 }
 
-fn power_up(mirror_cave: &HashMap<Coord, char>) -> usize {
+/// Finds the beam entering from the edge of the cave that energizes the most tiles.
+fn strongest_beam(mirror_cave: &HashMap<Coord, char>) -> (Beam, usize) {
     let (max_x, max_y) = mirror_cave
         .keys()
         .fold((0, 0), |(mx, my), Coord { x, y }| (mx.max(*x), my.max(*y)));
-    let (winner, power) = (0..=max_x)
+    (0..=max_x)
         .map(|x| Beam(Coord::from(x, 0), Direction::South))
         .chain((0..=max_x).map(|x| Beam(Coord::from(x, max_y), Direction::North)))
         .chain((0..=max_y).map(|y| Beam(Coord::from(0, y), Direction::East)))
         .chain((0..=max_y).map(|y| Beam(Coord::from(max_x, y), Direction::West)))
         .map(|b| (b, energize(mirror_cave, b).keys().count()))
         .max_by_key(|(_, power)| *power)
-        .unwrap();
-    if SHOW_MAP {
-        println!(
-            "{}",
-            show_beams(mirror_cave, &energize(mirror_cave, winner))
-        )
-    }
-    power
+        .unwrap()
 }
 
 fn energize(
     mirror_cave: &HashMap<Coord, char>,
     start_beam: Beam,
+) -> HashMap<Coord, HashSet<Direction>> {
+    energize_stepwise(mirror_cave, start_beam, |_| {})
+}
+
+/// Like [`energize`], but calls `on_step` with the energized tiles after every step the beams
+/// take.
+fn energize_stepwise(
+    mirror_cave: &HashMap<Coord, char>,
+    start_beam: Beam,
+    mut on_step: impl FnMut(&HashMap<Coord, HashSet<Direction>>),
 ) -> HashMap<Coord, HashSet<Direction>> {
     let mut energized = HashMap::<Coord, HashSet<Direction>>::new();
-    let mut add_beam = |Beam(pos, dir)| -> bool {
-        let directions = energized.entry(pos).or_insert(HashSet::new());
+    let add_beam = |energized: &mut HashMap<Coord, HashSet<Direction>>, Beam(pos, dir)| -> bool {
+        let directions = energized.entry(pos).or_default();
         directions.insert(dir)
     };
-    add_beam(start_beam);
+    add_beam(&mut energized, start_beam);
     // Iterate beams: from every active beam, travel from its current location based on the
     // obstacle it's at. The beam becomes inactive once it reaches the cave wall or merges
     // with an existing beam.
     successors(Some(vec![start_beam]), |beams| {
+        on_step(&energized);
         let new_beams = beams
             .iter()
             .flat_map(|b| travel(mirror_cave, b).into_iter())
-            .filter(|b @ Beam(pos, _)| mirror_cave.contains_key(pos) && add_beam(*b))
+            .filter(|b @ Beam(pos, _)| {
+                mirror_cave.contains_key(pos) && add_beam(&mut energized, *b)
+            })
             .collect_vec();
         Some(new_beams).filter(|b| !b.is_empty())
     })
@@ -170,6 +199,27 @@ fn colorize(token: char, brightness: usize) -> String {
     format!("\x1b[38;5;{color}m{token}\x1b[0m")
 }
 
+/// Draws the cave as an image: energized tiles glow brighter the more beam directions pass
+/// through them, unlit mirrors and splitters are gray.
+fn render_beams(
+    mirror_cave: &HashMap<Coord, char>,
+    beams: &HashMap<Coord, HashSet<Direction>>,
+) -> Image {
+    let (max_x, max_y) = mirror_cave
+        .keys()
+        .fold((0, 0), |(mx, my), Coord { x, y }| (mx.max(*x), my.max(*y)));
+    Image::render(Coord::origin(), Coord::from(max_x, max_y), 4, |c| {
+        match (beams.get(c).map(|d| d.len()), mirror_cave.get(c)) {
+            (Some(4), _) => [0xff, 0xff, 0xff],
+            (Some(3), _) => [0xff, 0xff, 0x00],
+            (Some(2), _) => [0xff, 0xaf, 0x00],
+            (Some(_), _) => [0xff, 0x5f, 0x00],
+            (None, Some('.')) | (None, None) => [0x1c, 0x1c, 0x1c],
+            (None, Some(_)) => [0x80, 0x80, 0x80],
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,7 +242,23 @@ mod tests {
 
     #[test]
     fn part2_test() {
-        assert_eq!(51, power_up(&read_cave(SAMPLE_INPUT)));
+        assert_eq!(51, strongest_beam(&read_cave(SAMPLE_INPUT)).1);
+    }
+
+    #[test]
+    fn render_beams_test() {
+        let cave = read_cave(SAMPLE_INPUT);
+        let beams = energize(&cave, Beam(Coord::origin(), Direction::East));
+        let image = render_beams(&cave, &beams);
+        // Every tile is drawn as 4 by 4 pixels
+        let tile = |x: usize, y: usize| image.pixel(x * 4 + 3, y * 4 + 3);
+        // The beam only crosses the top left tile going east, and the splitter next to it sends
+        // it both north and south.
+        assert_eq!([0xff, 0x5f, 0x00], tile(0, 0));
+        assert_eq!([0xff, 0xaf, 0x00], tile(1, 0));
+        // An unlit mirror and an unlit empty tile
+        assert_eq!([0x80, 0x80, 0x80], tile(4, 1));
+        assert_eq!([0x1c, 0x1c, 0x1c], tile(9, 9));
     }
 
     #[test]
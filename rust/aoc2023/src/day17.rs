@@ -5,7 +5,10 @@ use std::{
 
 use itertools::Itertools;
 
-use crate::util::{assign_coordinates, Coord, Direction};
+use crate::{
+    options::Options,
+    util::{assign_coordinates, Coord, Direction},
+};
 
 #[derive(PartialEq, Eq, Debug)]
 struct Step {
@@ -25,7 +28,7 @@ impl Ord for Step {
     }
 }
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let city = read_city(input)?;
     let part1 = part1(&city)?;
     println!("Part 1: {part1}");
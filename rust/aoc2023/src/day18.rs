@@ -5,7 +5,10 @@ use std::{
 
 use itertools::Itertools;
 
-use crate::util::{read, Coord, Direction};
+use crate::{
+    options::Options,
+    util::{read, Coord, Direction},
+};
 
 struct Instruction<'a> {
     command: char,
@@ -13,16 +16,24 @@ struct Instruction<'a> {
     color: &'a str,
 }
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
     let instructions = read_instructions(input)?;
     let part1 = part1(&instructions);
     println!("Part 1: {part1}");
+    if options.show {
+        println!("{}", show_lagoon(&dig_lagoon(&instructions)));
+    }
     let part2 = part2(&instructions);
     println!("Part 2: {part2}");
     Ok(())
 }
 
 fn part1(instructions: &Vec<Instruction>) -> usize {
+    dig_lagoon(instructions).len()
+}
+
+/// Digs the trench and the interior, returning all the dug out positions.
+fn dig_lagoon(instructions: &[Instruction]) -> HashSet<Coord> {
     let mut trench = HashSet::from([Coord::origin()]);
     instructions.iter().fold(
         Coord::origin(),
@@ -36,8 +47,32 @@ fn part1(instructions: &Vec<Instruction>) -> usize {
         },
     );
     flood_fill(&mut trench, Coord::from(1, 1));
+    trench
+}
 
-    trench.len()
+fn show_lagoon(lagoon: &HashSet<Coord>) -> String {
+    let (min, max) = lagoon.iter().fold(
+        (Coord::origin(), Coord::origin()),
+        |(min, max), Coord { x, y }| {
+            (
+                Coord::from(min.x.min(*x), min.y.min(*y)),
+                Coord::from(max.x.max(*x), max.y.max(*y)),
+            )
+        },
+    );
+    (min.y..=max.y)
+        .map(|y| {
+            (min.x..=max.x)
+                .map(|x| {
+                    if lagoon.contains(&Coord::from(x, y)) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>()
+        })
+        .join("\n")
 }
 
 fn part2(instructions: &Vec<Instruction>) -> i64 {
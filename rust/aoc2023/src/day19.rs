@@ -3,7 +3,7 @@ use std::{collections::HashMap, iter::successors};
 use itertools::Itertools;
 use regex::Regex;
 
-use crate::util::read;
+use crate::{options::Options, util::read};
 
 #[derive(Debug)]
 struct Workflow {
@@ -28,7 +28,7 @@ struct Part {
     s: i64,
 }
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let (workflow_map, parts) = read_input(input)?;
     let part1 = part1(&parts, &workflow_map);
     println!("Part 1: {part1}");
@@ -3,6 +3,12 @@ use std::collections::{HashMap, VecDeque};
 use itertools::Itertools;
 use regex::Regex;
 
+use crate::{
+    options::Options,
+    util::Coord,
+    viz::{Frames, Image},
+};
+
 struct ModuleDef<'a> {
     kind: char,
     name: String,
@@ -50,12 +56,17 @@ impl PulseCounter {
     }
 }
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
     let modules = read_modules(input);
     let part1 = part1(&modules);
     println!("Part 1: {}", part1);
     let part2 = part2(&modules);
     println!("Part 2: {}", part2);
+    if let Some(format) = &options.frames {
+        let mut frames = Frames::new("day20_modules", format)?;
+        animate_button_presses(&modules, 1000, &mut frames);
+        println!("Wrote {}", frames.finish()?);
+    }
     Ok(())
 }
 
@@ -83,6 +94,37 @@ fn part2(modules: &[ModuleDef<'_>]) -> i64 {
     -1
 }
 
+/// Captures the state of all modules after every button press. Modules are laid out in a square
+/// in order of their names: flip-flops are green when on, conjunctions are more purple the more
+/// of their inputs they last saw as high.
+fn animate_button_presses(modules: &[ModuleDef<'_>], presses: usize, frames: &mut Frames) {
+    let (edges, mut state) = initial_state(modules);
+    let mut counter = PulseCounter { low: 0, high: 0 };
+    let names = state.keys().sorted().cloned().collect_vec();
+    let width = (names.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let bottom_right = Coord::from(
+        width as i64 - 1,
+        (names.len().max(1) - 1) as i64 / width as i64,
+    );
+    for _ in 0..presses {
+        push_button(&mut counter, &mut state, &edges);
+        frames.capture(&Image::render(Coord::origin(), bottom_right, 8, |c| {
+            let index = (c.y * width as i64 + c.x) as usize;
+            match names.get(index).and_then(|name| state.get(name)) {
+                Some(Module::FlipFlop(true)) => [0x40, 0xc0, 0x40],
+                Some(Module::FlipFlop(false)) => [0x20, 0x40, 0x20],
+                Some(Module::Conjunction(inputs)) => {
+                    let high = inputs.values().filter(|&&on| on).count();
+                    let intensity = 0x40 + 0xa0 * high / inputs.len().max(1);
+                    [intensity as u8, 0x30, intensity as u8]
+                }
+                Some(_) => [0x80, 0x80, 0x80],
+                None => [0x1c, 0x1c, 0x1c],
+            }
+        }));
+    }
+}
+
 fn push_button(
     counter: &mut PulseCounter,
     state: &mut HashMap<String, Module>,
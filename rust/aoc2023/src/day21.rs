@@ -1,8 +1,11 @@
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
-use crate::util::{assign_coordinates, Coord, Step};
+use crate::{
+    options::Options,
+    util::{assign_coordinates, Coord, Step},
+};
 
-pub fn run(input: &str) -> Result<(), String> {
+pub fn run(input: &str, _options: &Options) -> Result<(), String> {
     let (rocks, start) = read_garden(input);
     let part1 = part1(&rocks, start);
     println!("Part 1: {part1}");
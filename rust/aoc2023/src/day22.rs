@@ -1,11 +1,20 @@
-use std::{collections::VecDeque, iter::repeat};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    fs,
+    iter::repeat,
+};
 
 use itertools::Itertools;
 use regex::Regex;
 
-use crate::util::read;
+use crate::{
+    options::{viz_path, Options},
+    util::{read, Coord},
+    viz::{palette, Frames, Image},
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Brick {
     from: Coord3,
     to: Coord3,
@@ -23,51 +32,68 @@ impl Brick {
             },
         }
     }
+    /// All (x, y) columns the brick occupies.
+    fn footprint(&self) -> impl Iterator<Item = Coord> + '_ {
+        (self.from.x..=self.to.x)
+            .flat_map(move |x| (self.from.y..=self.to.y).map(move |y| Coord::from(x, y)))
+    }
+}
+impl Display for Brick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}~{}", self.from, self.to)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Coord3 {
     x: i64,
     y: i64,
     z: i64,
 }
+impl Display for Coord3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.x, self.y, self.z)
+    }
+}
 
-pub fn run(input: &str) -> Result<(), String> {
-    let bricks = read_bricks(input)?;
-    check_bricks(&bricks);
+pub fn run(input: &str, options: &Options) -> Result<(), String> {
+    let bricks = validate_bricks(read_bricks(input)?).map_err(|e| e.to_string())?;
     let part1 = part1(&bricks);
     println!("Part 1: {part1}");
     let part2 = part2(&bricks);
     println!("Part 2: {part2}");
+    if let Some(format) = &options.export {
+        export(&bricks, format)?;
+    }
+    if let Some(format) = &options.frames {
+        let mut frames = Frames::new("day22_settling", format)?;
+        animate_settling(&bricks, &mut frames);
+        println!("Wrote {}", frames.finish()?);
+    }
     Ok(())
 }
 
 fn part1(bricks: &[Brick]) -> usize {
-    let dropped = drop_bricks(&bricks);
-    let supporting = determine_supports(&dropped);
-    (0..dropped.len())
-        .filter(|i| {
-            let supported_by = supporting
-                .iter()
-                .filter(|(from, _)| from == i)
-                .map(|(_, to)| to)
-                .collect_vec();
-            supported_by.iter().all(|&b| {
-                let other_supports = supporting
-                    .iter()
-                    .filter(|&(from, to)| to == b && from != i)
-                    .count();
-                other_supports > 0
-            })
-        })
-        .count()
+    let settled = settle(bricks);
+    let mut supporters = vec![0; settled.bricks.len()];
+    settled
+        .supports
+        .iter()
+        .for_each(|(_, above)| supporters[*above] += 1);
+    // A brick can be disintegrated safely unless it is the only supporter of another brick.
+    let load_bearing = settled
+        .supports
+        .iter()
+        .filter(|(_, above)| supporters[*above] == 1)
+        .map(|(below, _)| below)
+        .unique()
+        .count();
+    settled.bricks.len() - load_bearing
 }
 
 fn part2(bricks: &[Brick]) -> usize {
-    let stacked = drop_bricks(&bricks);
-    let node_count = stacked.len();
-    let edges = determine_supports(&stacked);
-    score_part2(edges, node_count)
+    let settled = settle(bricks);
+    score_part2(settled.supports, settled.bricks.len())
 }
 
 fn score_part2(edges: Vec<(usize, usize)>, node_count: usize) -> usize {
@@ -107,61 +133,260 @@ fn score_part2(edges: Vec<(usize, usize)>, node_count: usize) -> usize {
         .sum()
 }
 
-fn determine_supports(dropped: &Vec<Brick>) -> Vec<(usize, usize)> {
-    dropped
+/// Bricks after settling, in the order they came to rest, together with the support edges
+/// `(below, above)` for every pair of bricks that are directly stacked on each other.
+struct Settled {
+    bricks: Vec<Brick>,
+    supports: Vec<(usize, usize)>,
+}
+
+/// For every (x, y) column that contains a brick: the z of its highest cube and the index of the
+/// brick that cube belongs to.
+#[derive(Default)]
+struct HeightMap(HashMap<Coord, (i64, usize)>);
+
+/// Drops all bricks as far down as they go, lowest bricks first.
+fn settle(bricks: &[Brick]) -> Settled {
+    settle_in_order(bricks.iter().sorted_by_key(|b| b.from.z))
+}
+
+/// Settles the bricks in the order provided. Each brick can only come to rest on bricks that
+/// were provided before it, so the order must be bottom-up.
+fn settle_in_order<'a>(bricks: impl Iterator<Item = &'a Brick>) -> Settled {
+    let mut height_map = HeightMap::default();
+    let mut settled = Settled {
+        bricks: Vec::new(),
+        supports: Vec::new(),
+    };
+    for brick in bricks {
+        let id = settled.bricks.len();
+        let below = brick
+            .footprint()
+            .filter_map(|c| height_map.0.get(&c))
+            .copied()
+            .collect_vec();
+        let drop_to = below.iter().map(|(top, _)| top + 1).max().unwrap_or(1);
+        below
+            .iter()
+            .filter(|(top, _)| top + 1 == drop_to)
+            .map(|(_, supporter)| *supporter)
+            .unique()
+            .for_each(|supporter| settled.supports.push((supporter, id)));
+        let dropped = brick.drop_to(drop_to);
+        dropped.footprint().for_each(|c| {
+            height_map.0.insert(c, (dropped.to.z, id));
+        });
+        settled.bricks.push(dropped);
+    }
+    settled
+}
+
+/// Removes a brick from a settled stack, lets the rest settle again and counts how many bricks
+/// fell.
+fn fallen_without(settled: &Settled, removed: usize) -> usize {
+    let remaining = settled
+        .bricks
         .iter()
         .enumerate()
-        .flat_map(|(i, b)| {
-            dropped
-                .iter()
-                .enumerate()
-                .filter(|(_, c)| c.from.z == b.to.z + 1 && are_horizontally_intersected(b, c))
-                .map(move |(j, _)| (i, j))
-        })
-        .collect_vec()
+        .filter(|(i, _)| *i != removed)
+        .map(|(_, b)| b)
+        .collect_vec();
+    // The settled order is bottom-up, so it can be reused as is.
+    settle_in_order(remaining.iter().copied())
+        .bricks
+        .iter()
+        .zip(remaining)
+        .filter(|(after, before)| after.from.z != before.from.z)
+        .count()
 }
 
-fn drop_bricks(bricks: &[Brick]) -> Vec<Brick> {
-    let mut sorted_bricks = bricks
-        .iter()
-        .sorted_by(|a, b| a.from.z.cmp(&b.from.z))
-        .collect::<VecDeque<_>>();
-    let mut stacked_bricks = Vec::new();
-    while let Some(b) = sorted_bricks.pop_front() {
-        if b.from.z == 1 {
-            stacked_bricks.push(b.clone());
-            continue;
+fn export(bricks: &[Brick], format: &str) -> Result<(), String> {
+    match format {
+        "obj" => {
+            let settled = settle(bricks);
+            let scores = (0..settled.bricks.len())
+                .map(|i| fallen_without(&settled, i))
+                .collect_vec();
+            let path = viz_path("day22_bricks.obj");
+            fs::write(&path, to_obj(&settled.bricks, &scores)).map_err(|e| e.to_string())?;
+            println!("Exported settled bricks to {path}");
+            Ok(())
         }
-        let drop_to = stacked_bricks
+        _ => Err(format!("Export format {format} not supported")),
+    }
+}
+
+/// Renders the bricks as boxes in [Wavefront OBJ] format, using vertex colors to show the score:
+/// blue bricks can be disintegrated safely, red bricks make the most other bricks fall.
+/// OBJ is y-up, so the brick z coordinate is mapped to y.
+///
+/// [Wavefront OBJ]: https://en.wikipedia.org/wiki/Wavefront_.obj_file
+fn to_obj(bricks: &[Brick], scores: &[usize]) -> String {
+    let max_score = scores.iter().max().copied().unwrap_or(0).max(1) as f64;
+    let mut obj =
+        String::from("# Settled bricks, colored by number of bricks falling when removed\n");
+    bricks
+        .iter()
+        .zip(scores)
+        .enumerate()
+        .for_each(|(i, (b, score))| {
+            let heat = *score as f64 / max_score;
+            let (red, green, blue) = (heat, 0.2, 1.0 - heat);
+            // A brick covers its cubes entirely, so the box ends one unit after the `to` coordinate.
+            let (x0, y0, z0) = (b.from.x, b.from.y, b.from.z);
+            let (x1, y1, z1) = (b.to.x + 1, b.to.y + 1, b.to.z + 1);
+            obj.push_str(&format!("o brick_{i}\n"));
+            [
+                (x0, y0, z0),
+                (x1, y0, z0),
+                (x1, y1, z0),
+                (x0, y1, z0),
+                (x0, y0, z1),
+                (x1, y0, z1),
+                (x1, y1, z1),
+                (x0, y1, z1),
+            ]
             .iter()
-            .rev()
-            .filter(|s| are_horizontally_intersected(s, b))
-            .map(|s| s.to.z + 1)
-            .max()
-            .unwrap_or(1);
-        stacked_bricks.push(b.drop_to(drop_to));
+            .for_each(|(x, y, z)| {
+                obj.push_str(&format!("v {x} {z} {y} {red:.3} {green:.3} {blue:.3}\n"))
+            });
+            // OBJ vertex indices are 1-based and count across all objects in the file.
+            let base = i * 8;
+            [
+                [1, 2, 3, 4],
+                [8, 7, 6, 5],
+                [5, 6, 2, 1],
+                [6, 7, 3, 2],
+                [7, 8, 4, 3],
+                [8, 5, 1, 4],
+            ]
+            .iter()
+            .for_each(|face| {
+                obj.push_str(&format!(
+                    "f {}\n",
+                    face.iter().map(|v| (base + v).to_string()).join(" ")
+                ))
+            });
+        });
+    obj
+}
+
+/// Captures the bricks settling one at a time. Bricks that haven't settled yet are drawn at their
+/// starting position.
+fn animate_settling(bricks: &[Brick], frames: &mut Frames) {
+    // Settling handles the bricks in this order as well, so indices match up.
+    let pending = bricks
+        .iter()
+        .sorted_by_key(|b| b.from.z)
+        .cloned()
+        .collect_vec();
+    let settled = settle(bricks).bricks;
+    let max = bricks
+        .iter()
+        .fold(Coord3 { x: 0, y: 0, z: 0 }, |m, b| Coord3 {
+            x: m.x.max(b.to.x),
+            y: m.y.max(b.to.y),
+            z: m.z.max(b.to.z),
+        });
+    for i in 0..=settled.len() {
+        let current = settled[..i].iter().chain(&pending[i..]).collect_vec();
+        frames.capture(&render_side_views(&current, &max));
     }
-    stacked_bricks
+}
+
+/// Draws the bricks as seen from the front (x and z) and, next to that, from the side (y and z).
+/// Every brick has its own color; where bricks are behind each other, the nearest one is shown.
+fn render_side_views(bricks: &[&Brick], max: &Coord3) -> Image {
+    // Image coordinates per view, mapped to the depth and index of the nearest brick
+    let mut front = HashMap::<Coord, (i64, usize)>::new();
+    let mut side = HashMap::<Coord, (i64, usize)>::new();
+    let show = |view: &mut HashMap<Coord, (i64, usize)>, at: Coord, depth: i64, id: usize| {
+        let nearest = view.entry(at).or_insert((depth, id));
+        if depth < nearest.0 {
+            *nearest = (depth, id);
+        }
+    };
+    bricks.iter().enumerate().for_each(|(id, b)| {
+        for z in b.from.z..=b.to.z {
+            b.footprint().for_each(|c| {
+                show(&mut front, Coord::from(c.x, max.z - z), c.y, id);
+                show(&mut side, Coord::from(max.x + 2 + c.y, max.z - z), c.x, id);
+            })
+        }
+    });
+    let bottom_right = Coord::from(max.x + 2 + max.y, max.z);
+    Image::render(Coord::origin(), bottom_right, 4, |c| {
+        if c.x == max.x + 1 {
+            [0, 0, 0]
+        } else if c.y == max.z {
+            [0x80, 0x80, 0x80]
+        } else {
+            front
+                .get(c)
+                .or_else(|| side.get(c))
+                .map(|(_, id)| palette(*id))
+                .unwrap_or([0x1c, 0x1c, 0x1c])
+        }
+    })
 }
 
 fn are_horizontally_intersected(a: &Brick, b: &Brick) -> bool {
     a.from.x <= b.to.x && b.from.x <= a.to.x && a.from.y <= b.to.y && b.from.y <= a.to.y
 }
 
-/// Check that assumptions about the bricks are OK
-fn check_bricks(bricks: &[Brick]) {
+/// Ways in which a snapshot of falling bricks can violate the assumptions of the solver.
+#[derive(Debug, PartialEq, Eq)]
+enum BrickError {
+    /// The brick extends along more than one axis.
+    Diagonal(usize, Brick),
+    /// The two bricks occupy at least one cube in common.
+    Overlapping(usize, usize),
+    /// The lowest cube of the brick is not above the ground (z = 0).
+    BelowGround(usize, Brick),
+}
+impl Display for BrickError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Diagonal(i, b) => write!(f, "Brick {i} ({b}) extends along multiple axes"),
+            Self::Overlapping(i, j) => write!(f, "Bricks {i} and {j} overlap"),
+            Self::BelowGround(i, b) => write!(f, "Brick {i} ({b}) is below z=1"),
+        }
+    }
+}
+
+/// Checks that the assumptions about the bricks hold: every brick is a straight line of cubes
+/// above the ground, and no two bricks take up the same space. Bricks with their endpoints
+/// reversed are normalized so that `from` is never greater than `to`.
+fn validate_bricks(bricks: Vec<Brick>) -> Result<Vec<Brick>, BrickError> {
+    let bricks = bricks
+        .into_iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let diffs = [b.to.x - b.from.x, b.to.y - b.from.y, b.to.z - b.from.z];
+            match diffs.iter().filter(|d| **d != 0).count() {
+                0 | 1 if diffs.iter().any(|d| *d < 0) => Ok(Brick {
+                    from: b.to,
+                    to: b.from,
+                }),
+                0 | 1 => Ok(b),
+                _ => Err(BrickError::Diagonal(i, b)),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some((i, b)) = bricks.iter().find_position(|b| b.from.z < 1) {
+        return Err(BrickError::BelowGround(i, b.clone()));
+    }
     bricks
         .iter()
-        .map(|b| [b.to.x - b.from.x, b.to.y - b.from.y, b.to.z - b.from.z])
         .enumerate()
-        .for_each(|(i, diffs @ [dx, dy, dz])| {
-            if diffs.iter().filter(|d| **d > 0).count() > 1 {
-                println!("Brick {i} has multiple coordinate changes: {dx} {dy} {dz}");
-            }
-            if diffs.iter().filter(|d| **d < 0).count() > 0 {
-                println!("Brick {i} has a negative coordinate change: {dx} {dy} {dz}");
-            }
-        });
+        .tuple_combinations()
+        .find(|((_, a), (_, b))| {
+            are_horizontally_intersected(a, b) && a.from.z <= b.to.z && b.from.z <= a.to.z
+        })
+        .map_or(Ok(()), |((i, _), (j, _))| {
+            Err(BrickError::Overlapping(i, j))
+        })?;
+    Ok(bricks)
 }
 
 fn read_bricks(input: &str) -> Result<Vec<Brick>, String> {
@@ -204,18 +429,108 @@ mod tests {
         assert_eq!(true, are_horizontally_intersected(&bricks[0], &bricks[1]))
     }
 
+    #[test]
+    fn validate_bricks_test() {
+        let bricks = read_bricks(SAMPLE_INPUT).unwrap();
+        assert_eq!(Ok(bricks.clone()), validate_bricks(bricks));
+
+        let reversed = validate_bricks(read_bricks("1,2,1~1,0,1").unwrap()).unwrap();
+        assert_eq!("1,0,1~1,2,1", reversed[0].to_string());
+
+        let diagonal = read_bricks("0,0,1~0,0,1\n0,0,2~1,1,2").unwrap();
+        assert_eq!(
+            Err(BrickError::Diagonal(1, diagonal[1].clone())),
+            validate_bricks(diagonal)
+        );
+
+        let below_ground = read_bricks("0,0,0~0,0,1").unwrap();
+        assert_eq!(
+            Err(BrickError::BelowGround(0, below_ground[0].clone())),
+            validate_bricks(below_ground)
+        );
+
+        let overlapping = read_bricks("0,0,1~2,0,1\n5,5,1~5,5,3\n1,0,3~1,0,1").unwrap();
+        assert_eq!(
+            Err(BrickError::Overlapping(0, 2)),
+            validate_bricks(overlapping)
+        );
+    }
+
     #[test]
     fn part1_test() {
         let bricks = read_bricks(SAMPLE_INPUT).unwrap();
         assert_eq!(5, part1(&bricks));
     }
 
+    #[test]
+    fn part1_matches_fallen_without_test() {
+        // Bricks of length 1 to 3 along x or y, one per level so that none of them overlap
+        let bricks = (0..200)
+            .map(|i: i64| {
+                let (x, y, len) = ((i * 7) % 10, (i * 3) % 10, i % 3);
+                let to = if i % 2 == 0 {
+                    (x + len, y)
+                } else {
+                    (x, y + len)
+                };
+                format!("{x},{y},{}~{},{},{}", i + 1, to.0, to.1, i + 1)
+            })
+            .join("\n");
+        let bricks = read_bricks(&bricks).unwrap();
+        let settled = settle(&bricks);
+        let safe = (0..settled.bricks.len())
+            .filter(|&i| fallen_without(&settled, i) == 0)
+            .count();
+        assert_eq!(safe, part1(&bricks));
+    }
+
     #[test]
     fn part2_test() {
         let bricks = read_bricks(SAMPLE_INPUT).unwrap();
         assert_eq!(7, part2(&bricks));
     }
 
+    #[test]
+    fn settle_test() {
+        let settled = settle(&read_bricks(SAMPLE_INPUT).unwrap());
+        assert_eq!(
+            vec![1, 2, 2, 3, 3, 4, 5],
+            settled.bricks.iter().map(|b| b.from.z).collect_vec()
+        );
+        assert_eq!(
+            vec![
+                (0, 1),
+                (0, 2),
+                (1, 3),
+                (2, 3),
+                (1, 4),
+                (2, 4),
+                (3, 5),
+                (4, 5),
+                (5, 6)
+            ],
+            settled.supports
+        );
+    }
+
+    #[test]
+    fn fallen_without_test() {
+        let settled = settle(&read_bricks(SAMPLE_INPUT).unwrap());
+        assert_eq!(6, fallen_without(&settled, 0));
+        assert_eq!(1, fallen_without(&settled, 5));
+        assert_eq!(0, fallen_without(&settled, 6));
+    }
+
+    #[test]
+    fn to_obj_test() {
+        let settled = settle(&read_bricks("1,0,1~1,2,1\n1,1,3~1,1,3").unwrap());
+        let obj = to_obj(&settled.bricks, &[1, 0]);
+        assert_eq!(16, obj.lines().filter(|l| l.starts_with("v ")).count());
+        assert_eq!(12, obj.lines().filter(|l| l.starts_with("f ")).count());
+        assert!(obj.contains("o brick_1\nv 1 2 1 0.000 0.200 1.000\n"));
+        assert!(obj.ends_with("f 16 13 9 12\n"));
+    }
+
     #[test]
     fn score_part2_test() {
         let edges = vec![
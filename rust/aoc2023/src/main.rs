@@ -2,10 +2,10 @@
 use std::{
     env::{self, Args},
     fs,
-    num::ParseIntError,
 };
 
 use chrono::Datelike;
+use options::Options;
 
 mod day01;
 mod day02;
@@ -29,13 +29,15 @@ mod day19;
 mod day20;
 mod day21;
 mod day22;
+mod options;
 mod util;
+mod viz;
 
 fn main() -> Result<(), String> {
-    let puzzle = get_puzzle(env::args()).map_err(|e| e.to_string())?;
+    let (puzzle, options) = read_args(env::args())?;
     let day_runner = get_day_runner(puzzle).ok_or(format!("Day {puzzle} not supported"))?;
     let input = get_input(puzzle)?;
-    time(|| day_runner(&input))
+    time(|| day_runner(&input, &options))
 }
 
 fn time<T>(run: impl Fn() -> T) -> T {
@@ -45,11 +47,30 @@ fn time<T>(run: impl Fn() -> T) -> T {
     result
 }
 
-fn get_puzzle(args: Args) -> Result<u32, ParseIntError> {
-    args.skip(1)
-        .next()
-        .map(|s| s.parse())
-        .unwrap_or_else(|| Ok(chrono::Local::now().date_naive().day()))
+/** Reads the puzzle to run (defaults to today) and any options from the command line. */
+fn read_args(args: Args) -> Result<(u32, Options), String> {
+    let mut puzzle = None;
+    let mut options = Options::default();
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--export" => {
+                options.export = Some(args.next().ok_or("--export requires a format")?);
+            }
+            "--show" => options.show = true,
+            "--frames" => {
+                options.frames = Some(args.next().ok_or("--frames requires a format")?);
+            }
+            "--tilt" => {
+                options.tilt = Some(args.next().ok_or("--tilt requires a program")?);
+            }
+            day => puzzle = Some(util::read::<u32>(day)?),
+        }
+    }
+    Ok((
+        puzzle.unwrap_or_else(|| chrono::Local::now().date_naive().day()),
+        options,
+    ))
 }
 
 fn get_input(puzzle: u32) -> Result<String, String> {
@@ -57,7 +78,7 @@ fn get_input(puzzle: u32) -> Result<String, String> {
     fs::read_to_string(format!("../../input/{filename}")).map_err(|e| e.to_string())
 }
 
-type DayRunner = fn(&str) -> Result<(), String>;
+type DayRunner = fn(&str, &Options) -> Result<(), String>;
 fn get_day_runner(puzzle: u32) -> Option<&'static DayRunner> {
     RUNNERS.get(puzzle as usize - 1)
 }
@@ -0,0 +1,19 @@
+/// Settings read from the command line that days can use to produce output besides the answers.
+/// Days that have nothing to offer for a setting simply ignore it.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Format to export the puzzle's state to, e.g. `obj`.
+    pub export: Option<String>,
+    /// Image format to write every step of a simulation in, e.g. `png`.
+    pub frames: Option<String>,
+    /// Print visual debug output, such as maps, along with the answers.
+    pub show: bool,
+    /// Program of tilts to run on the day 14 platform, e.g. `NWSE*1000000000` or `N,E,N`.
+    pub tilt: Option<String>,
+}
+
+/// Path to write exports and visualizations to. Like the inputs, this path is relative to the
+/// `aoc2023` directory.
+pub fn viz_path(filename: &str) -> String {
+    format!("../../viz/{filename}")
+}
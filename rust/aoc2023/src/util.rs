@@ -2,6 +2,9 @@ use std::{fmt::Display, str::FromStr};
 
 use itertools::Itertools;
 
+pub mod cycle;
+pub mod math;
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct Coord {
     pub x: i64,
@@ -135,32 +138,3 @@ pub fn assign_coordinates(spec: &'_ str) -> impl Iterator<Item = (Coord, char)>
             .map(move |(x, c)| (Coord::from(x as i64, y as i64), c))
     })
 }
-
-pub fn lcm(a: i64, b: i64) -> i64 {
-    (a * b).abs() / gcd(a, b)
-}
-
-pub fn gcd(a: i64, b: i64) -> i64 {
-    match (a, b) {
-        (0, b) => b,
-        (a, 0) => a,
-        (a, b) if a < b => gcd(a, b.rem_euclid(a)),
-        (a, b) if b < a => gcd(b, a.rem_euclid(b)),
-        _ => panic!("never finished gcd"),
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn lcm_test() {
-        assert_eq!(15, lcm(3, 5))
-    }
-
-    #[test]
-    fn gcd_test() {
-        assert_eq!(6, gcd(48, 18))
-    }
-}
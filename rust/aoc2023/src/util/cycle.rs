@@ -0,0 +1,82 @@
+//! Finding where a repeatedly stepped state starts to repeat itself.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    hash::Hash,
+};
+
+/// The states visited from an initial state until the first repeat. From then on the states
+/// repeat every `length` steps.
+pub struct Cycle<T> {
+    /// Number of steps before the cycle starts.
+    pub prefix: usize,
+    /// Number of steps in the cycle.
+    pub length: usize,
+    /// The state after every step, starting with the initial state: `prefix + length` in total.
+    states: Vec<T>,
+}
+impl<T> Cycle<T> {
+    /// The state after the given number of steps.
+    pub fn after(&self, steps: u64) -> &T {
+        let (prefix, length) = (self.prefix as u64, self.length as u64);
+        let index = if steps < prefix {
+            steps
+        } else {
+            prefix + (steps - prefix) % length
+        };
+        &self.states[index as usize]
+    }
+}
+
+/// Steps from the initial state until a state is repeated, recognizing states by their key. The
+/// key only needs to tell states apart, so it can be cheaper to compare than the state itself.
+pub fn find_cycle<T, K: Hash + Eq>(
+    initial: T,
+    step: impl Fn(&T) -> T,
+    key: impl Fn(&T) -> K,
+) -> Cycle<T> {
+    let mut seen = HashMap::from([(key(&initial), 0)]);
+    let mut states = vec![initial];
+    loop {
+        let next = step(states.last().unwrap());
+        match seen.entry(key(&next)) {
+            Entry::Occupied(entry) => {
+                let prefix = *entry.get();
+                return Cycle {
+                    prefix,
+                    length: states.len() - prefix,
+                    states,
+                };
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(states.len());
+            }
+        }
+        states.push(next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_cycle_test() {
+        // 1, 2, 4, 8, 16, 12, 4, ... (mod 20)
+        let cycle = find_cycle(1, |n| n * 2 % 20, |n| *n);
+        assert_eq!((2, 4), (cycle.prefix, cycle.length));
+        assert_eq!(
+            vec![1, 2, 4, 8, 16, 12, 4, 8, 16],
+            (0..9).map(|n| *cycle.after(n)).collect::<Vec<_>>()
+        );
+        assert_eq!(&16, cycle.after(1_000_000_000_000));
+    }
+
+    #[test]
+    fn find_cycle_key_test() {
+        // the key ignores the step count, so the cycle is found in the value alone
+        let cycle = find_cycle((0, 5), |(i, n)| (i + 1, (n + 3) % 7), |(_, n)| *n);
+        assert_eq!((0, 7), (cycle.prefix, cycle.length));
+        assert_eq!(&(3, 0), cycle.after(10));
+    }
+}
@@ -0,0 +1,162 @@
+//! Number theory helpers that don't overflow on the way to a result that fits.
+
+/// The greatest common divisor. It is unsigned, as the gcd of `i64::MIN` and 0 doesn't fit in i64.
+pub fn gcd(a: i64, b: i64) -> u64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+pub fn gcd_i128(a: i128, b: i128) -> u128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The least common multiple, or `None` if it doesn't fit. The lcm with 0 is 0.
+pub fn checked_lcm(a: i64, b: i64) -> Option<i64> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    let lcm = (a.unsigned_abs() / gcd(a, b)).checked_mul(b.unsigned_abs())?;
+    lcm.try_into().ok()
+}
+
+pub fn checked_lcm_i128(a: i128, b: i128) -> Option<i128> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    let lcm = (a.unsigned_abs() / gcd_i128(a, b)).checked_mul(b.unsigned_abs())?;
+    lcm.try_into().ok()
+}
+
+/// Returns `(g, x, y)` such that `a * x + b * y = g`, where `g` is the gcd of `a` and `b`.
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (1, 0);
+    let (mut y0, mut y1) = (0, 1);
+    while r1 != 0 {
+        let q = r0.div_euclid(r1);
+        (r0, r1) = (r1, r0 - q * r1);
+        (x0, x1) = (x1, x0 - q * x1);
+        (y0, y1) = (y1, y0 - q * y1);
+    }
+    if r0 < 0 {
+        (-r0, -x0, -y0)
+    } else {
+        (r0, x0, y0)
+    }
+}
+
+/// The `x` in `0..m` with `a * x ≡ 1 (mod m)`, if `a` and `m` are coprime.
+pub fn mod_inverse(a: i128, m: i128) -> Option<i128> {
+    match extended_gcd(a.rem_euclid(m), m) {
+        (1, x, _) => Some(x.rem_euclid(m)),
+        _ => None,
+    }
+}
+
+/**
+Combines `t ≡ a (mod m)` and `t ≡ b (mod n)` into a single congruence `t ≡ c (mod lcm(m, n))`,
+returned as `(c, lcm(m, n))`. The moduli don't need to be coprime, in which case the congruences
+may contradict each other and there is no solution. Fails if the combined modulus doesn't fit.
+*/
+pub fn crt((a, m): (i128, i128), (b, n): (i128, i128)) -> Result<Option<(i128, i128)>, String> {
+    let overflow = || format!("Combining modulo {m} and {n} overflows");
+    let (a, b) = (a.rem_euclid(m), b.rem_euclid(n));
+    let g = i128::try_from(gcd_i128(m, n)).map_err(|_| overflow())?;
+    if (b - a) % g != 0 {
+        return Ok(None);
+    }
+    // t = a + m * k, where m * k ≡ b - a (mod n), so k ≡ (b - a) / g * (m / g)^-1 (mod n / g)
+    let reduced = n / g;
+    let inverse = mod_inverse(m / g, reduced).ok_or_else(overflow)?;
+    let k = ((b - a) / g)
+        .rem_euclid(reduced)
+        .checked_mul(inverse)
+        .ok_or_else(overflow)?
+        .rem_euclid(reduced);
+    let modulus = checked_lcm_i128(m, n).ok_or_else(overflow)?;
+    let c = (m * k)
+        .checked_add(a)
+        .ok_or_else(overflow)?
+        .rem_euclid(modulus);
+    Ok(Some((c, modulus)))
+}
+
+/// The largest integer whose square is at most `n`.
+pub fn isqrt(n: u128) -> u128 {
+    n.isqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gcd_test() {
+        assert_eq!(6, gcd(48, 18));
+        assert_eq!(6, gcd(-48, 18));
+        assert_eq!(5, gcd(5, 5));
+        assert_eq!(7, gcd(0, 7));
+        assert_eq!(0, gcd(0, 0));
+        assert_eq!(1 << 63, gcd(i64::MIN, 0));
+        assert_eq!(1 << 63, gcd(i64::MIN, i64::MIN));
+        assert_eq!(1, gcd(i64::MIN, i64::MAX));
+        assert_eq!(1 << 127, gcd_i128(i128::MIN, 0));
+        assert_eq!(2, gcd_i128(i128::MIN, 6));
+    }
+
+    #[test]
+    fn checked_lcm_test() {
+        assert_eq!(Some(15), checked_lcm(3, 5));
+        assert_eq!(Some(12), checked_lcm(4, -6));
+        assert_eq!(Some(0), checked_lcm(0, 5));
+        // The product overflows, but the lcm itself fits
+        assert_eq!(Some(i64::MAX), checked_lcm(i64::MAX, i64::MAX));
+        assert_eq!(None, checked_lcm(i64::MAX, 2));
+        assert_eq!(None, checked_lcm(i64::MIN, 1));
+        assert_eq!(None, checked_lcm_i128(i128::MIN, i128::MIN));
+        assert_eq!(
+            Some(2 * i64::MAX as i128),
+            checked_lcm_i128(i64::MAX.into(), 2)
+        );
+    }
+
+    #[test]
+    fn extended_gcd_test() {
+        let (g, x, y) = extended_gcd(240, 46);
+        assert_eq!(2, g);
+        assert_eq!(2, 240 * x + 46 * y);
+        assert_eq!((3, 0, -1), extended_gcd(0, -3));
+    }
+
+    #[test]
+    fn mod_inverse_test() {
+        assert_eq!(Some(4), mod_inverse(3, 11));
+        assert_eq!(Some(4), mod_inverse(-8, 11));
+        assert_eq!(None, mod_inverse(4, 6));
+    }
+
+    #[test]
+    fn crt_test() {
+        assert_eq!(Ok(Some((5, 6))), crt((2, 3), (1, 2)));
+        assert_eq!(Ok(Some((10, 12))), crt((4, 6), (2, 4)));
+        assert_eq!(Ok(Some((10, 12))), crt((-2, 6), (6, 4)));
+        assert_eq!(Ok(None), crt((1, 6), (2, 4)));
+        assert!(crt((0, i128::MAX), (1, i128::MAX - 1)).is_err());
+    }
+
+    #[test]
+    fn isqrt_test() {
+        assert_eq!(
+            vec![0, 1, 1, 1, 2, 2, 2, 2, 2, 3],
+            (0..10).map(isqrt).collect::<Vec<_>>()
+        );
+        assert_eq!(u64::MAX as u128, isqrt(u128::MAX));
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs;
+
+use crate::{options::viz_path, util::Coord};
+
+pub type Rgb = [u8; 3];
+
+/// An RGB image that can be written as PNG or PPM without pulling in an image library.
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+impl Image {
+    /// Renders the grid between `top_left` and `bottom_right` (inclusive), drawing every cell as a
+    /// `scale` by `scale` block in the color returned for it.
+    pub fn render(
+        top_left: Coord,
+        bottom_right: Coord,
+        scale: usize,
+        color: impl Fn(&Coord) -> Rgb,
+    ) -> Image {
+        let columns = (bottom_right.x - top_left.x + 1).max(0) as usize;
+        let rows = (bottom_right.y - top_left.y + 1).max(0) as usize;
+        let (width, height) = (columns * scale, rows * scale);
+        let mut pixels = vec![[0; 3]; width * height];
+        for row in 0..rows {
+            for col in 0..columns {
+                let cell = color(&top_left.move_by((col as i64, row as i64)));
+                for y in row * scale..(row + 1) * scale {
+                    pixels[y * width + col * scale..y * width + (col + 1) * scale].fill(cell);
+                }
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// The color of the pixel at `(x, y)`.
+    #[cfg(test)]
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Encodes the image as a binary [PPM] file.
+    ///
+    /// [PPM]: https://netpbm.sourceforge.net/doc/ppm.html
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        ppm.extend(self.pixels.iter().flatten());
+        ppm
+    }
+
+    /// Encodes the image as a [PNG] file. The image data is stored without compression, which
+    /// keeps the encoder small at the cost of larger files.
+    ///
+    /// [PNG]: https://www.w3.org/TR/png/
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut header = Vec::new();
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering and no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+        write_chunk(&mut png, b"IHDR", &header);
+        // Every scanline starts with its filter type, which is always 0 (None) here.
+        let scanlines = self
+            .pixels
+            .chunks(self.width.max(1))
+            .flat_map(|row| [0].into_iter().chain(row.iter().flatten().copied()))
+            .collect::<Vec<u8>>();
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Writes the image to the viz directory as `<name>.<format>`, where format is `png` or `ppm`.
+/// Returns the path written to.
+pub fn save(image: &Image, name: &str, format: &str) -> Result<String, String> {
+    let data = match format {
+        "png" => image.to_png(),
+        "ppm" => image.to_ppm(),
+        _ => return Err(format!("Image format {format} not supported")),
+    };
+    let path = viz_path(&format!("{name}.{format}"));
+    fs::write(&path, data).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Writes the frames of a simulation as numbered images into a directory in the viz directory,
+/// so they can be stepped through or turned into an animation with an external tool.
+/// Errors while writing are kept until [`Frames::finish`] so simulations don't need to handle them.
+pub struct Frames {
+    directory: String,
+    format: String,
+    count: usize,
+    error: Option<String>,
+}
+impl Frames {
+    /// Starts capturing frames into `viz/<name>/`. Frames left there by an earlier run are removed,
+    /// so they don't end up in the animation.
+    pub fn new(name: &str, format: &str) -> Result<Frames, String> {
+        if !["png", "ppm"].contains(&format) {
+            return Err(format!("Image format {format} not supported"));
+        }
+        let directory = viz_path(name);
+        fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+        for entry in fs::read_dir(&directory).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "png" || extension == "ppm")
+            {
+                fs::remove_file(&path).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(Frames {
+            directory,
+            format: format.to_owned(),
+            count: 0,
+            error: None,
+        })
+    }
+
+    pub fn capture(&mut self, image: &Image) {
+        if self.error.is_some() {
+            return;
+        }
+        let data = match self.format.as_str() {
+            "png" => image.to_png(),
+            _ => image.to_ppm(),
+        };
+        let path = format!("{}/{:05}.{}", self.directory, self.count, self.format);
+        self.count += 1;
+        self.error = fs::write(path, data).err().map(|e| e.to_string());
+    }
+
+    /// Reports where the frames were written, or the first error that occurred.
+    pub fn finish(self) -> Result<String, String> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(format!("{} frames in {}", self.count, self.directory)),
+        }
+    }
+}
+
+/// A distinct, reasonably bright color for every index, to tell neighboring objects apart.
+pub fn palette(index: usize) -> Rgb {
+    [
+        (64 + index * 67 % 192) as u8,
+        (64 + index * 131 % 192) as u8,
+        (64 + index * 29 % 192) as u8,
+    ]
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Wraps the data in a zlib stream consisting of uncompressed ("stored") deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xFFFF;
+    let mut zlib = vec![0x78, 0x01];
+    let block_count = data.len().div_ceil(MAX_BLOCK).max(1);
+    (0..block_count).for_each(|i| {
+        let block = &data[i * MAX_BLOCK..data.len().min((i + 1) * MAX_BLOCK)];
+        let is_final = (i + 1 == block_count) as u8;
+        let len = block.len() as u16;
+        zlib.push(is_final);
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend(block);
+    });
+    zlib.extend(adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |c, _| {
+            if c & 1 == 1 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            }
+        })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_test() {
+        assert_eq!(0xAE426082, crc32(b"IEND"));
+        assert_eq!(0x11E60398, adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn render_test() {
+        let image = Image::render(Coord::from(-1, 0), Coord::from(0, 1), 2, |c| {
+            [c.x as u8, c.y as u8, 0]
+        });
+        assert_eq!((4, 4), (image.width, image.height));
+        assert_eq!([255, 0, 0], image.pixels[1]);
+        assert_eq!([0, 0, 0], image.pixels[2]);
+        assert_eq!([0, 1, 0], image.pixels[15]);
+    }
+
+    #[test]
+    fn to_png_test() {
+        let png = Image::render(Coord::origin(), Coord::from(2, 1), 1, |_| [255, 0, 0]).to_png();
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[0..8]);
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!([0, 0, 0, 3, 0, 0, 0, 2], png[16..24]);
+        assert_eq!(b"IEND\xAE\x42\x60\x82", &png[png.len() - 8..]);
+    }
+
+    #[test]
+    fn frames_test() {
+        let image = Image::render(Coord::origin(), Coord::origin(), 1, |_| [0, 0, 0]);
+        let mut frames = Frames::new("frames_test", "ppm").unwrap();
+        (0..3).for_each(|_| frames.capture(&image));
+        frames.finish().unwrap();
+        // A second run with fewer frames doesn't leave the old ones behind
+        let mut frames = Frames::new("frames_test", "png").unwrap();
+        frames.capture(&image);
+        assert_eq!(
+            Ok(format!("1 frames in {}", viz_path("frames_test"))),
+            frames.finish()
+        );
+        let files = fs::read_dir(viz_path("frames_test"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        fs::remove_dir_all(viz_path("frames_test")).unwrap();
+        assert_eq!(vec!["00000.png"], files);
+    }
+}